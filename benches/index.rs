@@ -0,0 +1,84 @@
+//! Times `jd index` on a generated system of about 100,000 folders.
+//!
+//! Run with `cargo bench --bench index`.  The system has 10 areas of 10
+//! categories of 99 ids, and every id has 10 folders of its own, which
+//! indexing should not need to look inside.  It is generated once in
+//! cargo's temporary directory and reused.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+/// How many times each command is timed.
+const RUNS: usize = 5;
+/// How many folders each id has inside it.
+const FOLDERS_PER_ID: usize = 10;
+
+/// Generate the system, unless it was already generated.
+fn fixture() -> PathBuf {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("index_bench");
+    // Hidden, so indexing skips it.
+    let done = root.join(".generated");
+    if done.exists() {
+        return root;
+    }
+
+    let _ = fs::remove_dir_all(&root);
+    let mut folders = 0;
+    for area in (10..110).step_by(10) {
+        let area_folder = root.join(format!("{}-{}_area_{}", area % 100, area % 100 + 9, area));
+        for category in area % 100..area % 100 + 10 {
+            let category_folder = area_folder.join(format!("{:02}_category", category));
+            for id in 1..100 {
+                let id_folder = category_folder.join(format!("{:02}.{:02}_id", category, id));
+                for inner in 0..FOLDERS_PER_ID {
+                    fs::create_dir_all(id_folder.join(format!("folder_{}", inner)))
+                        .expect("Cannot create fixture");
+                    folders += 1;
+                }
+                folders += 1;
+            }
+            folders += 1;
+        }
+        folders += 1;
+    }
+    fs::write(&done, "").expect("Cannot create fixture");
+    println!("Generated {} folders in {}", folders, root.display());
+
+    root
+}
+
+/// Time `jd` with some arguments, returning the fastest and the median run.
+fn time(args: &[&str]) -> (Duration, Duration) {
+    let mut times: Vec<Duration> = (0..RUNS)
+        .map(|_| {
+            let start = Instant::now();
+            let status = Command::new(env!("CARGO_BIN_EXE_jd"))
+                .args(args)
+                .stdout(Stdio::null())
+                .status()
+                .expect("Cannot run jd");
+            assert!(status.success(), "jd {:?} failed", args);
+            start.elapsed()
+        })
+        .collect();
+    times.sort();
+    (times[0], times[RUNS / 2])
+}
+
+fn main() {
+    let root = fixture();
+    let root = root.to_str().expect("Fixture path is valid UTF-8");
+
+    for (name, args) in [
+        ("index", vec!["index", root]),
+        ("index --inside-ids", vec!["index", root, "--inside-ids"]),
+    ] {
+        let (fastest, median) = time(&args);
+        println!(
+            "{:<24} fastest {:>8.1?}   median {:>8.1?}   ({} runs)",
+            name, fastest, median, RUNS
+        );
+    }
+}
@@ -0,0 +1,142 @@
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::Match;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// The name of the files that list what to leave out of a system.
+pub const IGNORE_FILE: &str = ".jdignore";
+
+/// Decides which files and folders to leave out when walking a system.
+///
+/// A `.jdignore` uses the same patterns as a `.gitignore`, and applies to
+/// the folder it is in and everything below it.  Patterns in deeper
+/// folders win, so they can un-ignore things with `!`.  Hidden files and
+/// folders, whose names start with `.`, are left out too unless
+/// `include_hidden` is set.
+///
+/// The rules can be shared between threads walking different folders.
+pub struct IgnoreRules {
+    root: PathBuf,
+    include_hidden: bool,
+    /// The patterns of each folder seen so far, or `None` if it has no `.jdignore`.
+    folders: Mutex<HashMap<PathBuf, Option<Arc<Gitignore>>>>,
+}
+
+impl IgnoreRules {
+    /// Create the rules for walking the system at `root`.
+    pub fn new(root: &Path, include_hidden: bool) -> Self {
+        IgnoreRules {
+            root: root.to_path_buf(),
+            include_hidden,
+            folders: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Check if `path` should be left out.
+    ///
+    /// The root itself is never left out, even if it is hidden or is `.`.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path == self.root {
+            return false;
+        }
+        if !self.include_hidden && is_hidden(path) {
+            return true;
+        }
+
+        // The closest `.jdignore` that says anything about the path wins.
+        for folder in path.ancestors().skip(1) {
+            if !folder.starts_with(&self.root) {
+                break;
+            }
+            if let Some(rules) = self.rules_for(folder) {
+                match rules.matched(path, is_dir) {
+                    Match::Ignore(_) => return true,
+                    Match::Whitelist(_) => return false,
+                    Match::None => {}
+                }
+            }
+        }
+        false
+    }
+
+    /// Get the patterns of the `.jdignore` in `folder`, reading it the first time.
+    fn rules_for(&self, folder: &Path) -> Option<Arc<Gitignore>> {
+        let cached = self.folders.lock().unwrap().get(folder).cloned();
+        match cached {
+            Some(rules) => rules,
+            None => {
+                // Read the file without holding the lock, so other threads can go on.
+                let rules = load(folder).map(Arc::new);
+                self.folders
+                    .lock()
+                    .unwrap()
+                    .insert(folder.to_path_buf(), rules.clone());
+                rules
+            }
+        }
+    }
+}
+
+/// Checks if a given file or directory is hidden.
+fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .map(|name| name.to_string_lossy().starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Read the `.jdignore` in `folder`, if it has one.
+///
+/// Lines that are not valid patterns are skipped.
+fn load(folder: &Path) -> Option<Gitignore> {
+    let file = folder.join(IGNORE_FILE);
+    if !file.is_file() {
+        return None;
+    }
+
+    let mut builder = GitignoreBuilder::new(folder);
+    let _ = builder.add(file);
+    builder.build().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::IgnoreRules;
+    use crate::testing::TempRoot;
+    use std::fs;
+    use std::path::Path;
+
+    #[test]
+    fn test_ignore_rules() {
+        let root = TempRoot::new("ignore");
+        let category = root.join("10-19_finance/12_payroll");
+        fs::create_dir_all(&category).unwrap();
+        fs::write(root.join(".jdignore"), "node_modules\n*.bak\n/backup\n").unwrap();
+        fs::write(category.join(".jdignore"), "!keep.bak\ntarget/\n").unwrap();
+
+        let rules = IgnoreRules::new(&root, false);
+        assert!(!rules.is_ignored(&root, true));
+        assert!(rules.is_ignored(&root.join("node_modules"), true));
+        assert!(rules.is_ignored(&category.join("node_modules"), true));
+        assert!(rules.is_ignored(&root.join("backup"), true));
+        // anchored to the root
+        assert!(!rules.is_ignored(&category.join("backup"), true));
+        assert!(rules.is_ignored(&category.join("old.bak"), false));
+        assert!(!rules.is_ignored(&category.join("keep.bak"), false));
+        // only folders
+        assert!(rules.is_ignored(&category.join("target"), true));
+        assert!(!rules.is_ignored(&category.join("target"), false));
+        assert!(!rules.is_ignored(&category.join("12.01_sept_payroll"), true));
+
+        assert!(rules.is_ignored(&root.join(".git"), true));
+        let rules = IgnoreRules::new(&root, true);
+        assert!(!rules.is_ignored(&root.join(".git"), true));
+    }
+
+    #[test]
+    fn test_root_is_never_ignored() {
+        let rules = IgnoreRules::new(Path::new("."), false);
+        assert!(!rules.is_ignored(Path::new("."), true));
+        assert!(rules.is_ignored(Path::new("./.hidden"), true));
+    }
+}
@@ -0,0 +1,122 @@
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserializer, Serializer};
+use std::path::{Path, PathBuf};
+
+/// Get the raw bytes of a path.
+#[cfg(unix)]
+pub(crate) fn to_bytes(path: &Path) -> Vec<u8> {
+    use std::os::unix::ffi::OsStrExt;
+    path.as_os_str().as_bytes().to_vec()
+}
+
+/// Get the raw bytes of a path.
+///
+/// Outside of unix, names are not raw bytes, so this is only lossless for
+/// paths that are valid unicode.
+#[cfg(not(unix))]
+pub(crate) fn to_bytes(path: &Path) -> Vec<u8> {
+    path.to_string_lossy().into_owned().into_bytes()
+}
+
+/// Make a path from raw bytes.
+#[cfg(unix)]
+pub(crate) fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    use std::os::unix::ffi::OsStringExt;
+    PathBuf::from(std::ffi::OsString::from_vec(bytes))
+}
+
+/// Make a path from raw bytes.
+#[cfg(not(unix))]
+pub(crate) fn from_bytes(bytes: Vec<u8>) -> PathBuf {
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+/// Serialize a path without losing names that are not valid UTF-8.
+///
+/// Use with `#[serde(with = "lossless_path")]`.  Paths that are valid
+/// UTF-8 are written as strings, so indexes stay readable; other paths
+/// are written as a list of their raw bytes.
+pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+    if !serializer.is_human_readable() {
+        return serializer.serialize_bytes(&to_bytes(path));
+    }
+    match path.to_str() {
+        Some(text) => serializer.serialize_str(text),
+        None => serializer.collect_seq(to_bytes(path)),
+    }
+}
+
+/// Deserialize a path written by [`serialize`].
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+    if deserializer.is_human_readable() {
+        deserializer.deserialize_any(PathVisitor)
+    } else {
+        deserializer.deserialize_byte_buf(PathVisitor)
+    }
+}
+
+struct PathVisitor;
+
+impl<'de> Visitor<'de> for PathVisitor {
+    type Value = PathBuf;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("a path, as a string or a list of bytes")
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<PathBuf, E> {
+        Ok(PathBuf::from(value))
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<PathBuf, E> {
+        Ok(from_bytes(value.to_vec()))
+    }
+
+    fn visit_byte_buf<E: de::Error>(self, value: Vec<u8>) -> Result<PathBuf, E> {
+        Ok(from_bytes(value))
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<PathBuf, A::Error> {
+        let mut bytes = Vec::new();
+        while let Some(byte) = seq.next_element::<u8>()? {
+            bytes.push(byte);
+        }
+        Ok(from_bytes(bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::{Deserialize, Serialize};
+    use std::path::PathBuf;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Wrapper {
+        #[serde(with = "crate::jdnumber::lossless_path")]
+        path: PathBuf,
+    }
+
+    #[test]
+    fn test_utf8_path() {
+        let wrapper = Wrapper {
+            path: PathBuf::from("jd/10-19_finance/12_payroll"),
+        };
+        let text = ron::to_string(&wrapper).unwrap();
+        assert_eq!(text, r#"(path:"jd/10-19_finance/12_payroll")"#);
+        assert_eq!(ron::from_str::<Wrapper>(&text).unwrap(), wrapper);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let wrapper = Wrapper {
+            path: PathBuf::from(OsStr::from_bytes(b"caf\xe9")),
+        };
+        let text = ron::to_string(&wrapper).unwrap();
+        assert_eq!(text, "(path:[99,97,102,233])");
+        assert_eq!(ron::from_str::<Wrapper>(&text).unwrap(), wrapper);
+    }
+}
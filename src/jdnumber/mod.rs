@@ -1,14 +1,136 @@
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+
+pub mod lossless_path;
 use std::{
     cmp,
     path::{self, PathBuf},
+    str::FromStr,
+    sync::LazyLock,
 };
 
+// The patterns of the parts of a JD path.  They are compiled once, because
+// indexing matches them against every folder in the system.
+pub(crate) static PROJECT_AREA_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d\d\d)-(\d\d\d)(\D.*)$").expect("Hardcoded regex is valid."));
+pub(crate) static PROJECT_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d\d\d)([^0-9.].*)$").expect("Hardcoded regex is valid."));
+pub(crate) static AREA_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d\d)-(\d\d)(\D.*)$").expect("Hardcoded regex is valid."));
+pub(crate) static CATEGORY_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d\d)([^0-9.].*)$").expect("Hardcoded regex is valid."));
+pub(crate) static JD_EX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?m)^(\d\d\d)?\.?(\d\d)\.(\d\d\d?)(\D.*)$").expect("Hardcoded regex is valid")
+});
+/// PRO.AC.ID or AC.ID, on its own.  The ID can have three digits.
+pub(crate) static NUMBER_EX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(\d\d\d)?\.?(\d\d)\.(\d\d\d?)$").expect("Hardcoded regex is valid.")
+});
+
+/// Get the range of an area from its folder name, like `(10, 19)` for `10-19_finance`.
+pub fn area_range(name: &str) -> Option<(u32, u32)> {
+    let caps = AREA_EX.captures(name)?;
+    Some((caps[1].parse().ok()?, caps[2].parse().ok()?))
+}
+
+/// Get the number of a category from its folder name, like `12` for `12_payroll`.
+pub fn category_number(name: &str) -> Option<u32> {
+    if AREA_EX.is_match(name) {
+        return None;
+    }
+    CATEGORY_EX.captures(name)?[1].parse().ok()
+}
+
+/// Get the number of a project from its folder name, like `101` for `101_website`.
+pub fn project_number(name: &str) -> Option<u32> {
+    if PROJECT_AREA_EX.is_match(name) {
+        return None;
+    }
+    PROJECT_EX.captures(name)?[1].parse().ok()
+}
+
+/// How the folders of a Johnny Decimal system are laid out on disk.
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub enum Layout {
+    /// Areas, categories and ids all have folders:
+    /// `10-19_area/12_category/12.01_label`
+    #[default]
+    Full,
+    /// Category folders sit directly in the root folder:
+    /// `12_category/12.01_label`
+    NoArea,
+    /// Id folders sit directly in area folders, so the category
+    /// only exists as part of the id: `10-19_area/12.01_label`
+    CategoryOnly,
+}
+
+impl Layout {
+    /// Check if this layout has a folder for each area.
+    pub fn has_areas(&self) -> bool {
+        *self != Layout::NoArea
+    }
+
+    /// Check if this layout has a folder for each category.
+    pub fn has_categories(&self) -> bool {
+        *self != Layout::CategoryOnly
+    }
+}
+
+impl FromStr for Layout {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "full" => Ok(Layout::Full),
+            "no-area" => Ok(Layout::NoArea),
+            "category-only" => Ok(Layout::CategoryOnly),
+            _ => Err(String::from(
+                "unknown layout.  Use full, no-area or category-only.",
+            )),
+        }
+    }
+}
+
 /// A location of a Johnny Decimal number.
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
 pub enum Location {
-    Path(path::PathBuf),
+    /// A folder on disk.
+    Path(#[serde(with = "lossless_path")] path::PathBuf),
+    /// A single file on disk, for example `12.03_invoice.pdf`.
+    File(#[serde(with = "lossless_path")] path::PathBuf),
+    /// A web page, mail folder or anything else with a URL.
+    Url(String),
+    /// Something physical, like a box on a shelf.
+    Physical { description: String },
+    /// A copy of the folder somewhere else, like a cloud-synced mirror.
+    Mirror(#[serde(with = "lossless_path")] path::PathBuf),
+}
+
+impl Location {
+    /// Get the path on disk of this location, if it has one.
+    pub fn path(&self) -> Option<&path::Path> {
+        match self {
+            Location::Path(path) | Location::File(path) | Location::Mirror(path) => Some(path),
+            Location::Url(_) | Location::Physical { .. } => None,
+        }
+    }
+
+    /// Check if this location is a single file rather than a folder.
+    pub fn is_file(&self) -> bool {
+        matches!(self, Location::File(_))
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Location::Path(path) => write!(f, "folder: {}", path.display()),
+            Location::File(path) => write!(f, "file: {}", path.display()),
+            Location::Url(url) => write!(f, "url: {}", url),
+            Location::Physical { description } => write!(f, "physical: {}", description),
+            Location::Mirror(path) => write!(f, "mirror: {}", path.display()),
+        }
+    }
 }
 
 /// A Johnny.Decimal number.
@@ -22,10 +144,24 @@ pub struct JdNumber {
     pub project: Option<u32>,
     /// The project label, for example 101**_project_1**
     pub project_label: Option<String>,
+    /// The label of the project area the project is filed in, for
+    /// example 100-199**_projects**.
+    ///
+    /// Projects can also sit directly in the root folder, without a
+    /// project area.
+    #[serde(default)]
+    pub project_area_label: Option<String>,
     /// The category, between 0 and 99.
     pub category: u32,
-    /// The id, between 0 and 99.
+    /// The id, between 0 and 99, or 999 if it is extended.
     pub id: u32,
+    /// The id has three digits, like `12.001`.
+    ///
+    /// Categories that need more than 99 ids can be extended, so that all
+    /// their ids have three digits.  The ids of one category all have the
+    /// same number of digits.
+    #[serde(default)]
+    pub extended: bool,
     /// The label, for example 50.42**_this_is_the_label**.
     pub label: String,
     /// The area label:
@@ -34,9 +170,14 @@ pub struct JdNumber {
     pub category_label: String,
     /// The path of the JD number relative to the system root.
     pub path: Location,
+    /// Other places the JD number can be found.
+    #[serde(default)]
+    pub locations: Vec<Location>,
 }
 impl JdNumber {
     /// Create a new JD number, with some error checking.
+    ///
+    /// Ids above 99 are extended.
     pub fn new(
         area_label: &str,
         category_label: &str,
@@ -48,7 +189,7 @@ impl JdNumber {
         path: PathBuf,
     ) -> Result<Self, ()> {
         // If the area or category are too long, return none
-        if category > 99 || id > 99 {
+        if category > 99 || id > 999 {
             return Err(());
         }
 
@@ -65,29 +206,123 @@ impl JdNumber {
         return Ok(JdNumber {
             category,
             id,
+            extended: id > 99,
             project,
             project_label,
+            project_area_label: None,
             label,
             area_label: area_label.to_string(),
             category_label: category_label.to_string(),
             path: Location::Path(path),
+            locations: Vec::new(),
         });
     }
 
+    /// Get every location of a JD number, starting with `path`.
+    pub fn all_locations(&self) -> impl Iterator<Item = &Location> {
+        std::iter::once(&self.path).chain(self.locations.iter())
+    }
+
+    /// Get the primary location of a JD number.
+    ///
+    /// This is the first location on disk, preferring the folder or file
+    /// in the system itself over mirrors.
+    pub fn primary_location(&self) -> Option<&Location> {
+        self.all_locations()
+            .find(|l| matches!(l, Location::Path(_) | Location::File(_)))
+            .or_else(|| {
+                self.all_locations()
+                    .find(|l| matches!(l, Location::Mirror(_)))
+            })
+    }
+
+    /// Get just the number of a JD number, without its label.
+    ///
+    /// This returns a string like `12.01`, `12.001` or `101.12.01`.
+    pub fn number(&self) -> String {
+        let width = if self.extended { 3 } else { 2 };
+        match self.project {
+            Some(project) => format!(
+                "{:0>3}.{:0>2}.{:0>width$}",
+                project,
+                self.category,
+                self.id,
+                width = width
+            ),
+            None => format!("{:0>2}.{:0>width$}", self.category, self.id, width = width),
+        }
+    }
+
+    /// Get the range of the area of a JD number, like `10-19` or `00-09`.
+    pub fn get_area_range(&self) -> String {
+        let area = self.category / 10 * 10;
+        format!("{:0>2}-{:0>2}", area, area + 9)
+    }
+
     /// Get the area+label of a JD number.
     ///
     /// This returns a string in the format
     /// `50-59_area_label`
     pub fn get_area(&self) -> String {
-        format!(
-            "{area}0-{area}9{}",
-            self.area_label,
-            area = self.category.to_string().chars().nth(0).unwrap()
-        )
+        format!("{}{}", self.get_area_range(), self.area_label)
+    }
+
+    /// Get the category+label of a JD number, like `12_payroll`.
+    pub fn get_category(&self) -> String {
+        format!("{:0>2}{}", self.category, self.category_label)
+    }
+
+    /// Get the names of the project, area and category of a JD number,
+    /// like `101_website`, `10-19_design` and `12_mockups`.
+    ///
+    /// The project is left out if the number is not in one.  These are the
+    /// levels a system is shown in, whatever its layout on disk.
+    pub fn get_parents(&self) -> Vec<String> {
+        let mut parents: Vec<String> = self.get_project().into_iter().collect();
+        parents.push(self.get_area());
+        parents.push(self.get_category());
+        parents
+    }
+
+    /// Get the highest id in the category of this number.
+    ///
+    /// This is 99, or 999 if the category is extended.
+    pub fn max_id(&self) -> u32 {
+        if self.extended {
+            999
+        } else {
+            99
+        }
+    }
+
+    /// Get the project area+label of a JD number.
+    ///
+    /// This returns a string like `100-199_projects`, or `None` if the
+    /// number is not in a project area.
+    pub fn get_project_area(&self) -> Option<String> {
+        let start = self.project? / 100 * 100;
+        let label = self.project_area_label.as_ref()?;
+        Some(format!("{}-{}{}", start, start + 99, label))
+    }
+
+    /// Get the project+label of a JD number.
+    ///
+    /// This returns a string like `101_website`, or `None` if the
+    /// number is not in a project.
+    pub fn get_project(&self) -> Option<String> {
+        let project = self.project?;
+        Some(format!(
+            "{:0>3}{}",
+            project,
+            self.project_label.as_deref().unwrap_or("")
+        ))
     }
 
     /// Get the relative path of a JD number.
-    pub fn get_relative_path(&self) -> PathBuf {
+    ///
+    /// Area and category folders are only included when
+    /// the layout has them.
+    pub fn get_relative_path(&self, layout: Layout) -> PathBuf {
         // format!(
         //     "{}/{:0>2}{}/{:0>2}.{:0>2}{}",
         //     self.get_area(),
@@ -100,38 +335,24 @@ impl JdNumber {
 
         let mut path = PathBuf::new();
 
-        if self.project.is_some() && self.project_label.is_some() {
-            path.push(format!(
-                "{:0>3}{}",
-                self.project.unwrap(),
-                self.project_label.as_ref().unwrap()
-            ))
+        if let Some(project_area) = self.get_project_area() {
+            path.push(project_area);
+        }
+        if self.project_label.is_some() {
+            if let Some(project) = self.get_project() {
+                path.push(project);
+            }
         }
 
-        path.push(self.get_area());
-        path.push(format!(
-            "{:0>2}{}",
-            self.category,
-            self.category_label.clone()
-        ));
+        if layout.has_areas() {
+            path.push(self.get_area());
+        }
+        if layout.has_categories() {
+            path.push(self.get_category());
+        }
         // path.push(self.category_label.clone());
 
-        if self.project.is_none() {
-            path.push(format!(
-                "{:0>2}.{:0>2}{}",
-                self.category,
-                self.id,
-                self.label.clone()
-            ));
-        } else {
-            path.push(format!(
-                "{:0>3}.{:0>2}.{:0>2}{}",
-                self.project.unwrap(),
-                self.category,
-                self.id,
-                self.label.clone()
-            ))
-        }
+        path.push(format!("{}{}", self.number(), self.label));
         // path.push(format!("{:0>2}", self.id));
         // path.push(self.label.clone());
         path
@@ -144,40 +365,48 @@ impl JdNumber {
         println!("{:?}\n{:?}\n\n", jd1, jd2);
         return jd1.project == jd2.project
             && jd1.project_label == jd2.project_label
+            && jd1.project_area_label == jd2.project_area_label
             && jd1.category == jd2.category
             && jd1.id == jd2.id
+            && jd1.extended == jd2.extended
             && jd1.label == jd2.label
             && jd1.area_label == jd2.area_label
             && jd1.category_label == jd2.category_label
-            && jd1.path == jd2.path;
+            && jd1.path == jd2.path
+            && jd1.locations == jd2.locations;
     }
 }
 
 /// Create a johnny decimal number from a path.
+///
+/// The path is expected to use the full layout.
 impl TryFrom<PathBuf> for JdNumber {
     type Error = &'static str;
     // 20-29_testing/20_good_testing/20.35_test/
 
     fn try_from(path_value: PathBuf) -> Result<Self, Self::Error> {
-        //let path_value = PathBuf::from(&value);
+        JdNumber::from_path(path_value, Layout::Full)
+    }
+}
 
-        // TODO think about lazily compiling these regi.
-        // See https://docs.rs/regex/latest/regex/#example-avoid-compiling-the-same-regex-in-a-loop
+impl JdNumber {
+    /// Create a johnny decimal number from a path laid out with `layout`.
+    ///
+    /// Labels for areas or categories that have no folder in the layout
+    /// are left empty.
+    pub fn from_path(path_value: PathBuf, layout: Layout) -> Result<Self, &'static str> {
+        //let path_value = PathBuf::from(&value);
 
-        // initialize regi (plural of regex!)
-        let project_area_ex =
-            Regex::new(r"^(\d\d\d)-(\d\d\d)(\D.*)$").expect("Hardcoded regex is valid.");
-        let project_ex = Regex::new(r"^(\d\d\d)([^0-9.].*)$").expect("Hardcoded regex is valid.");
-        let area_ex = Regex::new(r"^(\d\d)-(\d\d)(\D.*)$").expect("Hardcoded regex is valid.");
-        let category_ex = Regex::new(r"^(\d\d)([^0-9.].*)$").expect("Hardcoded regex is valid.");
-        // let jd_ex =
-        //     Regex::new(r"^(\d\d\d)?\.?(\d)(\d)\.(\d\d)(.*)$").expect("Hardcoded regex is valid.");
-        let jd_ex = Regex::new(r"(?m)^(\d\d\d)?\.?(\d\d)\.(\d\d)(\D.*)$")
-            .expect("Hardcoded regex is valid");
+        // regi (plural of regex!)
+        let project_area_ex = &*PROJECT_AREA_EX;
+        let project_ex = &*PROJECT_EX;
+        let area_ex = &*AREA_EX;
+        let category_ex = &*CATEGORY_EX;
+        let jd_ex = &*JD_EX;
 
         // Initialize variable
-        let mut _project_area: Option<(&str, &str)> = None;
-        let mut _project_area_name: Option<&str> = None;
+        let mut project_area: Option<(u32, u32)> = None;
+        let mut project_area_name: Option<&str> = None;
         let mut project_name: Option<&str> = None;
         let mut project: Option<u32> = None;
         let mut area_name: Option<&str> = None;
@@ -188,82 +417,121 @@ impl TryFrom<PathBuf> for JdNumber {
         let mut _jd_area: Option<u32> = None;
         let mut jd_category: Option<u32> = None;
         let mut jd_id: Option<u32> = None;
+        let mut jd_extended = false;
         let mut jd_name: Option<&str> = None;
 
+        // The position of the component that held the JD id.
+        let mut jd_position: Option<usize> = None;
+        let component_count = path_value.components().count();
+
         // Extract all the components
-        for component in path_value.components() {
-            match project_area_ex.captures(component.as_os_str().to_str().unwrap()) {
-                Some(caps) => {
-                    _project_area =
-                        Some((caps.get(1).unwrap().as_str(), caps.get(2).unwrap().as_str()));
-                    // project_area_name = Some(caps.get(3).unwrap().as_str());
-                    _project_area_name = caps.get(3).map(|v| v.as_str());
+        for (position, component) in path_value.components().enumerate() {
+            // Names that are not UTF-8 cannot be part of a JD number, so
+            // skip them; if it is the last one, the path is not a JD number.
+            let component = match component.as_os_str().to_str() {
+                Some(component) => component,
+                None if position + 1 == component_count => {
+                    return Err("Name is not valid UTF-8");
                 }
-                None => {}
+                None => continue,
+            };
+
+            if let Some(caps) = project_area_ex.captures(component) {
+                project_area = Some((
+                    caps.get(1).unwrap().as_str().parse().unwrap(),
+                    caps.get(2).unwrap().as_str().parse().unwrap(),
+                ));
+                project_area_name = caps.get(3).map(|v| v.as_str());
+                // `100-199_x` would also look like a project.
+                continue;
             }
 
-            match project_ex.captures(component.as_os_str().to_str().unwrap()) {
-                Some(caps) => {
-                    // project_name = Some(caps.get(2).unwrap().as_str());
-                    project_name = caps.get(2).map(|v| v.as_str());
-                    // project = Some(caps.get(1).unwrap().as_str());
-                    project = caps.get(1).map(|v| v.as_str().parse().unwrap());
-                }
-                None => {}
+            if let Some(caps) = project_ex.captures(component) {
+                // project_name = Some(caps.get(2).unwrap().as_str());
+                project_name = caps.get(2).map(|v| v.as_str());
+                // project = Some(caps.get(1).unwrap().as_str());
+                project = caps.get(1).map(|v| v.as_str().parse().unwrap());
             }
 
-            match area_ex.captures(component.as_os_str().to_str().unwrap()) {
-                Some(caps) => {
-                    area = Some((
-                        caps.get(1).unwrap().as_str().parse().unwrap(),
-                        caps.get(2).unwrap().as_str().parse().unwrap(),
-                    ));
-                    // area_name = Some(caps.get(3).unwrap().as_str());
-                    area_name = caps.get(3).map(|v| v.as_str());
-                }
-                None => {}
+            if let Some(caps) = area_ex.captures(component) {
+                area = Some((
+                    caps.get(1).unwrap().as_str().parse().unwrap(),
+                    caps.get(2).unwrap().as_str().parse().unwrap(),
+                ));
+                // area_name = Some(caps.get(3).unwrap().as_str());
+                area_name = caps.get(3).map(|v| v.as_str());
+                // `10-19_x` would also look like a category.
+                continue;
             }
 
-            match category_ex.captures(component.as_os_str().to_str().unwrap()) {
-                Some(caps) => {
-                    category = caps.get(1).map(|v| v.as_str().parse().unwrap());
-                    category_name = caps.get(2).map(|v| v.as_str());
-                }
-                None => {}
+            if let Some(caps) = category_ex.captures(component) {
+                category = caps.get(1).map(|v| v.as_str().parse().unwrap());
+                category_name = caps.get(2).map(|v| v.as_str());
             }
 
-            match jd_ex.captures(component.as_os_str().to_str().unwrap()) {
-                Some(caps) => {
-                    jd_project = caps.get(1).map(|v| v.as_str().parse().unwrap());
-                    // jd_area = caps.get(2).map(|v| v.as_str().parse().unwrap());
-                    jd_category = caps.get(2).map(|v| v.as_str().parse().unwrap());
-                    jd_id = caps.get(3).map(|v| v.as_str().parse().unwrap());
-                    jd_name = caps.get(4).map(|v| v.as_str());
-                }
-                None => {}
+            if let Some(caps) = jd_ex.captures(component) {
+                jd_project = caps.get(1).map(|v| v.as_str().parse().unwrap());
+                // jd_area = caps.get(2).map(|v| v.as_str().parse().unwrap());
+                jd_category = caps.get(2).map(|v| v.as_str().parse().unwrap());
+                jd_id = caps.get(3).map(|v| v.as_str().parse().unwrap());
+                jd_extended = caps[3].len() == 3;
+                jd_name = caps.get(4).map(|v| v.as_str());
+                jd_position = Some(position);
             }
         }
 
+        // Only the last component can be the id; anything below
+        // an id folder is just content of that id.
+        if jd_position.map(|p| p + 1) != Some(component_count) {
+            return Err("Path does not end in a JD number");
+        }
+
         if project != jd_project {
             return Err("");
         }
 
+        match (project, project_area) {
+            (Some(project), Some((start, end))) => {
+                if start % 100 != 0 || end != start + 99 {
+                    return Err("Project area is not like 100-199.");
+                }
+                if project < start || project > end {
+                    return Err("Project is not in its project area.");
+                }
+            }
+            // A project area with no project in it is just a folder.
+            (None, Some(_)) => project_area_name = None,
+            _ => {}
+        }
+
+        // Without category folders the category comes from the id.
+        if !layout.has_categories() && category.is_none() {
+            category = jd_category;
+            category_name = Some("");
+        }
+
         if category != jd_category {
             return Err("");
         }
 
-        // If the first area number is not a multiple
-        // of ten, error.
-        if area.ok_or("")?.0 % 10 != 0 {
-            return Err("First area number is not a multiple of 10.");
-        }
-        // if the second area number is not 9 more than the first one,
-        // error.
-        if area.ok_or("")?.1 != area.ok_or("")?.0 + 9 {
-            return Err("Second area number is not 9 more than the first number.");
+        match area {
+            Some((start, end)) => {
+                // If the first area number is not a multiple
+                // of ten, error.
+                if start % 10 != 0 {
+                    return Err("First area number is not a multiple of 10.");
+                }
+                // if the second area number is not 9 more than the first one,
+                // error.
+                if end != start + 9 {
+                    return Err("Second area number is not 9 more than the first number.");
+                }
+            }
+            None if !layout.has_areas() => area_name = Some(""),
+            None => return Err("Could not find area name"),
         }
 
-        return match JdNumber::new(
+        let mut jd = JdNumber::new(
             area_name.ok_or("Could not find area name")?,
             category_name.ok_or("Could not find category name")?,
             category.ok_or("Could not find category")?,
@@ -272,10 +540,12 @@ impl TryFrom<PathBuf> for JdNumber {
             project_name.map(|p| p.to_string()),
             jd_name.ok_or("Could not find JD name")?.to_string(),
             path_value.clone(),
-        ) {
-            Ok(value) => Ok(value),
-            Err(_) => Err("Could not create JD number"),
-        };
+        )
+        .map_err(|_| "Could not create JD number")?;
+        jd.project_area_label = project_area_name.map(|p| p.to_string());
+        jd.extended = jd_extended;
+
+        Ok(jd)
     }
 }
 
@@ -284,10 +554,11 @@ impl TryFrom<String> for JdNumber {
 
     fn try_from(value: String) -> Result<Self, &'static str> {
         // PRO.AC.ID or AC.ID
-        let ex = Regex::new(r"^(\d\d\d)?\.?(\d\d)\.(\d\d)$").expect("Hardcoded regex is valid.");
+        let ex = &*NUMBER_EX;
         let project: Option<u32>;
         let category: u32;
         let id: u32;
+        let extended: bool;
 
         match ex.captures(&value) {
             Some(caps) => {
@@ -300,6 +571,7 @@ impl TryFrom<String> for JdNumber {
                     .get(3)
                     .map(|v| v.as_str().parse().unwrap())
                     .ok_or("Could not get id.")?;
+                extended = caps[3].len() == 3;
             }
             None => return Err("Regex did not match"),
         };
@@ -314,7 +586,10 @@ impl TryFrom<String> for JdNumber {
             "label".to_string(),
             PathBuf::new(),
         ) {
-            Ok(jd) => Ok(jd),
+            Ok(mut jd) => {
+                jd.extended = extended;
+                Ok(jd)
+            }
             Err(_err) => Err("Could not create JD"),
         };
     }
@@ -322,28 +597,7 @@ impl TryFrom<String> for JdNumber {
 
 impl std::fmt::Display for JdNumber {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self.project {
-            Some(project) => {
-                write!(
-                    f,
-                    "{:0>3}.{:0>2}.{:0>2}{}",
-                    project.to_string(),       //.red(),
-                    self.category.to_string(), //.red(),
-                    self.id.to_string(),       //.red(),
-                    self.label,                //.red()
-                )
-            }
-
-            None => {
-                write!(
-                    f,
-                    "{:0>2}.{:0>2}{}",
-                    self.category.to_string(), //.red(),
-                    self.id.to_string(),       //.red(),
-                    self.label,                //.red()
-                )
-            }
-        }
+        write!(f, "{}{}", self.number(), self.label)
     }
 }
 
@@ -428,7 +682,7 @@ mod tests {
 
     use crate::JdNumber;
 
-    use crate::jdnumber::Location;
+    use crate::jdnumber::{Layout, Location};
 
     #[test]
     fn test_jd_creation() {
@@ -484,12 +738,15 @@ mod tests {
             JdNumber {
                 category: 20,
                 id: 35,
+                extended: false,
                 project: None,
                 project_label: None,
+                project_area_label: None,
                 label: String::from("_test"),
                 category_label: String::from("_good_testing"),
                 area_label: String::from("_testing"),
-                path: Location::Path(PathBuf::from("20-29_testing/20_good_testing/20.35_test"))
+                path: Location::Path(PathBuf::from("20-29_testing/20_good_testing/20.35_test")),
+                locations: Vec::new(),
             }
         );
         assert_eq!(
@@ -497,12 +754,15 @@ mod tests {
             JdNumber {
                 category: 50,
                 id: 32,
+                extended: false,
                 project: None,
                 project_label: None,
+                project_area_label: None,
                 label: String::from("_label"),
                 area_label: String::from("_hi"),
                 category_label: String::from("_bye"),
-                path: Location::Path(PathBuf::from("50-59_hi/50_bye/50.32_label"))
+                path: Location::Path(PathBuf::from("50-59_hi/50_bye/50.32_label")),
+                locations: Vec::new(),
             }
         );
         assert_eq!(
@@ -513,14 +773,17 @@ mod tests {
             JdNumber {
                 category: 22,
                 id: 02,
+                extended: false,
                 project: Some(102),
                 project_label: Some("_grade-10".to_string()),
+                project_area_label: Some("_school".to_string()),
                 label: String::from("_oreo_project"),
                 category_label: String::from("-ap_biology"),
                 area_label: String::from("_RHS"),
                 path: Location::Path(PathBuf::from(
                     "100-199_school/102_grade-10/20-29_RHS/22-ap_biology/102.22.02_oreo_project"
-                ))
+                )),
+                locations: Vec::new(),
             }
         );
 
@@ -529,12 +792,15 @@ mod tests {
             JdNumber {
                 category: 12,
                 id: 02,
+                extended: false,
                 project: None,
                 project_label: None,
+                project_area_label: None,
                 label: String::from("_a_payroll"),
                 category_label: String::from("_payroll"),
                 area_label: String::from("_finance"),
-                path: Location::Path(PathBuf::from("10-19_finance/12_payroll/12.02_a_payroll"))
+                path: Location::Path(PathBuf::from("10-19_finance/12_payroll/12.02_a_payroll")),
+                locations: Vec::new(),
             }
         ));
         // assert_eq!(
@@ -557,6 +823,207 @@ mod tests {
         //assert!(JdNumber::try_from(String::from("500.42.31")).is_err());
     }
 
+    #[test]
+    fn test_jd_from_file_path() {
+        let jd = JdNumber::try_from(PathBuf::from("10-19_finance/12_payroll/12.03_invoice.pdf"))
+            .unwrap();
+        assert_eq!(jd.category, 12);
+        assert_eq!(jd.id, 3);
+        assert_eq!(jd.label, "_invoice.pdf");
+        assert_eq!(
+            jd.get_relative_path(Layout::Full),
+            PathBuf::from("10-19_finance/12_payroll/12.03_invoice.pdf")
+        );
+
+        // things inside an id folder are not ids themselves.
+        assert!(JdNumber::try_from(PathBuf::from(
+            "10-19_finance/12_payroll/12.02_oct_payroll/slip.pdf"
+        ))
+        .is_err());
+        assert!(JdNumber::try_from(PathBuf::from(
+            "10-19_finance/12_payroll/12.02_oct_payroll/drafts"
+        ))
+        .is_err());
+        assert!(JdNumber::try_from(PathBuf::new()).is_err());
+    }
+
+    #[test]
+    fn test_jd_project_area() {
+        let path = "100-199_school/102_grade-10/20-29_RHS/22_ap_biology/102.22.02_oreo_project";
+        let jd = JdNumber::try_from(PathBuf::from(path)).unwrap();
+        assert_eq!(jd.project_area_label, Some("_school".to_string()));
+        assert_eq!(jd.get_project_area(), Some("100-199_school".to_string()));
+        assert_eq!(jd.get_project(), Some("102_grade-10".to_string()));
+        assert_eq!(jd.get_relative_path(Layout::Full), PathBuf::from(path));
+
+        // projects do not need a project area
+        let path = "102_grade-10/20-29_RHS/22_ap_biology/102.22.02_oreo_project";
+        let jd = JdNumber::try_from(PathBuf::from(path)).unwrap();
+        assert_eq!(jd.project_area_label, None);
+        assert_eq!(jd.get_relative_path(Layout::Full), PathBuf::from(path));
+
+        // a project area with no project is just a folder
+        let jd = JdNumber::try_from(PathBuf::from(
+            "100-199_school/20-29_RHS/22_ap_biology/22.02_oreo_project",
+        ))
+        .unwrap();
+        assert_eq!(jd.project_area_label, None);
+        assert_eq!(jd.get_project(), None);
+
+        assert!(JdNumber::try_from(PathBuf::from(
+            "200-299_school/102_grade-10/20-29_RHS/22_ap_biology/102.22.02_oreo_project"
+        ))
+        .is_err());
+        assert!(JdNumber::try_from(PathBuf::from(
+            "100-150_school/102_grade-10/20-29_RHS/22_ap_biology/102.22.02_oreo_project"
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn test_jd_extended() {
+        let path = "10-19_finance/12_payroll/12.001_sept_payroll";
+        let jd = JdNumber::try_from(PathBuf::from(path)).unwrap();
+        assert!(jd.extended);
+        assert_eq!(jd.id, 1);
+        assert_eq!(jd.max_id(), 999);
+        assert_eq!(jd.to_string(), "12.001_sept_payroll");
+        assert_eq!(jd.get_relative_path(Layout::Full), PathBuf::from(path));
+
+        let jd = JdNumber::try_from(String::from("101.12.120")).unwrap();
+        assert!(jd.extended);
+        assert_eq!(jd.number(), "101.12.120");
+
+        // ids above 99 sort after the two digit ones
+        let low = JdNumber::try_from(String::from("12.099")).unwrap();
+        let high = JdNumber::try_from(String::from("12.100")).unwrap();
+        assert!(high > low);
+
+        assert!(JdNumber::try_from(String::from("12.1000")).is_err());
+    }
+
+    #[test]
+    fn test_jd_from_path_layouts() {
+        // category folders directly in the root
+        let jd = JdNumber::from_path(
+            PathBuf::from("12_payroll/12.01_sept_payroll"),
+            Layout::NoArea,
+        )
+        .unwrap();
+        assert_eq!(jd.category, 12);
+        assert_eq!(jd.id, 1);
+        assert_eq!(jd.area_label, "");
+        assert_eq!(jd.category_label, "_payroll");
+        assert_eq!(
+            jd.get_relative_path(Layout::NoArea),
+            PathBuf::from("12_payroll/12.01_sept_payroll")
+        );
+        assert!(
+            JdNumber::from_path(PathBuf::from("12_payroll/12.01_sept_payroll"), Layout::Full)
+                .is_err()
+        );
+
+        // id folders directly in area folders
+        let jd = JdNumber::from_path(
+            PathBuf::from("10-19_finance/12.01_sept_payroll"),
+            Layout::CategoryOnly,
+        )
+        .unwrap();
+        assert_eq!(jd.category, 12);
+        assert_eq!(jd.area_label, "_finance");
+        assert_eq!(jd.category_label, "");
+        assert_eq!(
+            jd.get_relative_path(Layout::CategoryOnly),
+            PathBuf::from("10-19_finance/12.01_sept_payroll")
+        );
+
+        // a full path still works with the other layouts
+        let jd = JdNumber::from_path(
+            PathBuf::from("10-19_finance/12_payroll/12.01_sept_payroll"),
+            Layout::NoArea,
+        )
+        .unwrap();
+        assert_eq!(jd.area_label, "_finance");
+    }
+
+    #[test]
+    fn test_layout_from_str() {
+        assert_eq!("full".parse::<Layout>(), Ok(Layout::Full));
+        assert_eq!("no-area".parse::<Layout>(), Ok(Layout::NoArea));
+        assert_eq!("category-only".parse::<Layout>(), Ok(Layout::CategoryOnly));
+        assert!("sideways".parse::<Layout>().is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_jd_from_non_utf8_path() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        // A folder above the system with a Latin-1 name.
+        let mut path = PathBuf::from(OsStr::from_bytes(b"/backup/caf\xe9"));
+        path.push("10-19_finance/12_payroll/12.01_sept_payroll");
+        let jd = JdNumber::try_from(path.clone()).unwrap();
+        assert_eq!(jd.category, 12);
+        assert_eq!(jd.id, 1);
+        assert_eq!(jd.path, Location::Path(path));
+
+        // A JD number with a Latin-1 label.
+        let mut path = PathBuf::from("10-19_finance/12_payroll");
+        path.push(OsStr::from_bytes(b"12.02_d\xe9cembre"));
+        assert_eq!(
+            JdNumber::try_from(path).unwrap_err(),
+            "Name is not valid UTF-8"
+        );
+    }
+
+    #[test]
+    fn test_location() {
+        let folder = Location::Path(PathBuf::from("12.01_sept_payroll"));
+        let file = Location::File(PathBuf::from("12.03_invoice.pdf"));
+
+        assert!(!folder.is_file());
+        assert!(file.is_file());
+        assert_eq!(file.path().unwrap(), PathBuf::from("12.03_invoice.pdf"));
+
+        let url = Location::Url("https://example.com/payroll".to_string());
+        assert!(url.path().is_none());
+        assert_eq!(url.to_string(), "url: https://example.com/payroll");
+    }
+
+    #[test]
+    fn test_primary_location() {
+        let mut jd = JdNumber::new(
+            "_finance",
+            "_payroll",
+            12,
+            1,
+            None,
+            None,
+            "_sept_payroll".to_string(),
+            PathBuf::from("12.01_sept_payroll"),
+        )
+        .unwrap();
+        jd.locations
+            .push(Location::Mirror(PathBuf::from("/mnt/cloud/12.01")));
+        jd.locations.push(Location::Physical {
+            description: "Box 3, top shelf".to_string(),
+        });
+
+        assert_eq!(jd.all_locations().count(), 3);
+        assert_eq!(jd.primary_location(), Some(&jd.path));
+
+        // without a folder in the system, the mirror is used.
+        jd.path = Location::Url("https://example.com/payroll".to_string());
+        assert_eq!(
+            jd.primary_location(),
+            Some(&Location::Mirror(PathBuf::from("/mnt/cloud/12.01")))
+        );
+
+        jd.locations.remove(0);
+        assert_eq!(jd.primary_location(), None);
+    }
+
     #[test]
     fn test_jd_from_string() {
         // Test PRO.AC.ID
@@ -613,6 +1080,37 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_jd_number() {
+        assert_eq!(
+            JdNumber::try_from(String::from("12.01")).unwrap().number(),
+            "12.01"
+        );
+        assert_eq!(
+            JdNumber::try_from(String::from("101.02.05"))
+                .unwrap()
+                .number(),
+            "101.02.05"
+        );
+    }
+
+    #[test]
+    fn test_jd_parents() {
+        let jd = JdNumber::try_from(PathBuf::from("00-09_system/05_inbox/05.01_scans")).unwrap();
+        assert_eq!(jd.get_area_range(), "00-09");
+        assert_eq!(jd.get_area(), "00-09_system");
+        assert_eq!(jd.get_parents(), vec!["00-09_system", "05_inbox"]);
+
+        let jd = JdNumber::try_from(PathBuf::from(
+            "100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page",
+        ))
+        .unwrap();
+        assert_eq!(
+            jd.get_parents(),
+            vec!["101_website", "10-19_design", "12_mockups"]
+        );
+    }
+
     #[test]
     fn test_jd_equality() {
         let jd_1 = JdNumber::new(
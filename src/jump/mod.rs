@@ -0,0 +1,179 @@
+use crate::jdnumber::JdNumber;
+use crate::prompt::readable;
+use crate::system::{System, AREA_INPUT_EX, CATEGORY_INPUT_EX};
+use rust_fuzzy_search::fuzzy_compare;
+use std::path::PathBuf;
+
+/// How alike a label has to be to the search to match at all, from 0 to 1.
+const THRESHOLD: f32 = 0.3;
+/// How much better than the next best a match has to be to be chosen by itself.
+const MARGIN: f32 = 0.2;
+/// How many matches are offered to choose from.
+const CHOICES: usize = 10;
+
+/// Where some input to `jd path` goes.
+#[derive(Debug, PartialEq)]
+pub enum Target<'a> {
+    /// A JD number, found by its label.
+    Id(&'a JdNumber),
+    /// The folder of a category or an area.
+    Folder(PathBuf),
+    /// JD numbers that match about as well as each other, best first.
+    Choices(Vec<&'a JdNumber>),
+}
+
+/// Work out where some input that is not a full JD number goes.
+///
+/// A category like `12` or `101.12`, or an area like `10-19`, goes to its
+/// folder.  Anything else is searched for in the labels: first for labels
+/// with words starting with every word of the input, like
+/// [`System::search_labels`], and then for labels that are spelled alike.
+pub fn resolve<'a>(system: &'a System, input: &str) -> Result<Target<'a>, String> {
+    let input = input.trim();
+
+    if CATEGORY_INPUT_EX.is_match(input) {
+        let jd = first(system, input, "Cannot find that category.")?;
+        if !system.layout.has_categories() {
+            return Err(String::from("This system has no category folders."));
+        }
+        let mut path = system.get_path(jd);
+        path.pop();
+        return Ok(Target::Folder(path));
+    }
+    if AREA_INPUT_EX.is_match(input) {
+        let jd = first(system, input, "Cannot find that area.")?;
+        if !system.layout.has_areas() {
+            return Err(String::from("This system has no area folders."));
+        }
+        let mut path = system.get_path(jd);
+        path.pop();
+        if system.layout.has_categories() {
+            path.pop();
+        }
+        return Ok(Target::Folder(path));
+    }
+
+    let search = input.to_lowercase();
+    let score = |jd: &JdNumber| fuzzy_compare(&search, &readable(&jd.label).to_lowercase());
+
+    let words = system.search_labels(input);
+    if !words.is_empty() {
+        // A label that is exactly the search beats the others.
+        let exact: Vec<&JdNumber> = words
+            .iter()
+            .copied()
+            .filter(|jd| readable(&jd.label).to_lowercase() == search)
+            .collect();
+        return Ok(match (words.len(), exact.len()) {
+            (1, _) => Target::Id(words[0]),
+            (_, 1) => Target::Id(exact[0]),
+            _ => Target::Choices(best(words, score)),
+        });
+    }
+
+    let mut scored: Vec<(&JdNumber, f32)> = system
+        .id
+        .iter()
+        .map(|jd| (jd, score(jd)))
+        .filter(|(_, score)| *score >= THRESHOLD)
+        .collect();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    match scored.as_slice() {
+        [] => Err(String::from("No JD numbers match.")),
+        [(jd, _)] => Ok(Target::Id(jd)),
+        [(jd, score), (_, next), ..] if score - next >= MARGIN => Ok(Target::Id(jd)),
+        _ => Ok(Target::Choices(
+            scored.into_iter().take(CHOICES).map(|(jd, _)| jd).collect(),
+        )),
+    }
+}
+
+/// Get the first JD number in a category or area.
+fn first<'a>(system: &'a System, input: &str, missing: &str) -> Result<&'a JdNumber, String> {
+    system
+        .select(Some(input.to_string()))
+        .map_err(String::from)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| String::from(missing))
+}
+
+/// Sort JD numbers by a score, best first, and keep the best few.
+fn best(mut numbers: Vec<&JdNumber>, score: impl Fn(&JdNumber) -> f32) -> Vec<&JdNumber> {
+    // The sort is stable, so ties stay in number order.
+    numbers.sort_by(|a, b| score(b).total_cmp(&score(a)));
+    numbers.truncate(CHOICES);
+    numbers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{resolve, Target};
+    use crate::jdnumber::Layout;
+    use crate::system::System;
+    use crate::testing::system_from_paths;
+    use std::path::{Path, PathBuf};
+
+    fn sample_system() -> System {
+        system_from_paths(
+            Path::new("/jd"),
+            &[
+                "10-19_finance/12_payroll/12.01_sept_payroll",
+                "10-19_finance/12_payroll/12.02_oct_payroll",
+                "10-19_finance/13_bookkeeping/13.01_ledger",
+                "20-29_admin/22_contracts/22.01_cleaning_contract",
+            ],
+        )
+    }
+
+    fn number(target: Target) -> String {
+        match target {
+            Target::Id(jd) => jd.number(),
+            other => panic!("expected an id, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_label() {
+        let system = sample_system();
+        assert_eq!(number(resolve(&system, "payroll oct").unwrap()), "12.02");
+        assert_eq!(number(resolve(&system, "Ledger").unwrap()), "13.01");
+        // misspelled
+        assert_eq!(
+            number(resolve(&system, "clenaing contract").unwrap()),
+            "22.01"
+        );
+
+        match resolve(&system, "payroll").unwrap() {
+            Target::Choices(choices) => {
+                let numbers: Vec<String> = choices.iter().map(|jd| jd.number()).collect();
+                assert_eq!(numbers, vec!["12.01", "12.02"]);
+            }
+            other => panic!("expected choices, got {:?}", other),
+        }
+
+        assert!(resolve(&system, "xyzzy").is_err());
+    }
+
+    #[test]
+    fn test_resolve_folder() {
+        let mut system = sample_system();
+        assert_eq!(
+            resolve(&system, "12").unwrap(),
+            Target::Folder(PathBuf::from("/jd/10-19_finance/12_payroll"))
+        );
+        assert_eq!(
+            resolve(&system, "20-29").unwrap(),
+            Target::Folder(PathBuf::from("/jd/20-29_admin"))
+        );
+        assert!(resolve(&system, "14").is_err());
+        assert!(resolve(&system, "10-29").is_err());
+
+        system.layout = Layout::NoArea;
+        assert!(resolve(&system, "10-19").is_err());
+        assert_eq!(
+            resolve(&system, "12").unwrap(),
+            Target::Folder(PathBuf::from("/jd/12_payroll"))
+        );
+    }
+}
@@ -0,0 +1,226 @@
+use crate::jdnumber::{JdNumber, Location};
+use qrcode::{Color, QrCode};
+use std::fmt::Write;
+use std::str::FromStr;
+
+/// The width of an A4 sheet, in millimetres.
+const PAGE_WIDTH: f64 = 210.0;
+/// The height of an A4 sheet, in millimetres.
+const PAGE_HEIGHT: f64 = 297.0;
+/// The size of a label, in millimetres.
+///
+/// This matches common 14-per-sheet label paper (99.1mm x 38.1mm).
+const LABEL_WIDTH: f64 = 99.1;
+const LABEL_HEIGHT: f64 = 38.1;
+const COLUMNS: usize = 2;
+const ROWS: usize = 7;
+/// The space around the labels on a sheet, in millimetres.
+const MARGIN_LEFT: f64 = 4.65;
+const MARGIN_TOP: f64 = 15.15;
+const COLUMN_GAP: f64 = 2.5;
+/// The space between the edge of a label and what is printed on it.
+const PADDING: f64 = 3.0;
+
+/// The format to write labels in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelFormat {
+    Svg,
+}
+
+impl FromStr for LabelFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "svg" => Ok(LabelFormat::Svg),
+            _ => Err(String::from("unknown label format.  Use svg.")),
+        }
+    }
+}
+
+/// Render printable labels for some JD numbers.
+///
+/// Each label has the number and title, the category and area,
+/// where the number is physically filed if known, and a QR code of the number.
+pub fn render(ids: &[&JdNumber], format: LabelFormat) -> Result<String, &'static str> {
+    match format {
+        LabelFormat::Svg => render_svg(ids),
+    }
+}
+
+/// Render a sheet of labels as an SVG.
+///
+/// Sheets are A4, and if there are more labels than fit on one sheet
+/// the sheets are stacked below each other.
+fn render_svg(ids: &[&JdNumber]) -> Result<String, &'static str> {
+    let per_page = COLUMNS * ROWS;
+    let pages = ids.len().div_ceil(per_page).max(1);
+    let height = PAGE_HEIGHT * pages as f64;
+
+    let mut svg = String::new();
+    // Writing to a string cannot fail.
+    let _ = writeln!(
+        svg,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w}mm" height="{h}mm" viewBox="0 0 {w} {h}">"#,
+        w = PAGE_WIDTH,
+        h = height
+    );
+    let _ = writeln!(
+        svg,
+        r#"<rect width="{}" height="{}" fill="white"/>"#,
+        PAGE_WIDTH, height
+    );
+
+    for (i, jd) in ids.iter().enumerate() {
+        let page = i / per_page;
+        let row = (i % per_page) / COLUMNS;
+        let column = i % COLUMNS;
+
+        let x = MARGIN_LEFT + column as f64 * (LABEL_WIDTH + COLUMN_GAP);
+        let y = page as f64 * PAGE_HEIGHT + MARGIN_TOP + row as f64 * LABEL_HEIGHT;
+
+        svg.push_str(&render_svg_label(jd, x, y)?);
+    }
+
+    svg.push_str("</svg>\n");
+    Ok(svg)
+}
+
+/// Render one label with its top left corner at `x`, `y`.
+fn render_svg_label(jd: &JdNumber, x: f64, y: f64) -> Result<String, &'static str> {
+    let mut label = String::new();
+
+    let _ = writeln!(
+        label,
+        r##"<g transform="translate({x:.2} {y:.2})"><rect width="{}" height="{}" rx="2" fill="none" stroke="#cccccc" stroke-width="0.2"/>"##,
+        LABEL_WIDTH, LABEL_HEIGHT
+    );
+
+    // The QR code fills the height of the label on the left.
+    let qr_size = LABEL_HEIGHT - 2.0 * PADDING;
+    label.push_str(&render_svg_qr(&jd.number(), PADDING, PADDING, qr_size)?);
+
+    // The text goes to the right of the QR code.
+    let text_x = qr_size + 2.0 * PADDING;
+    let text_width = LABEL_WIDTH - text_x - PADDING;
+
+    let title = jd.to_string();
+    // Shrink long titles so they fit; glyphs are roughly 0.6em wide.
+    let title_size = (text_width / (0.6 * title.chars().count() as f64)).min(6.0);
+
+    let mut lines = vec![
+        (title, title_size, "bold"),
+        (jd.get_category(), 3.5, "normal"),
+        (jd.get_area(), 3.5, "normal"),
+    ];
+    for location in jd.all_locations() {
+        if let Location::Physical { description } = location {
+            lines.push((description.clone(), 3.5, "normal"));
+        }
+    }
+
+    let mut text_y = PADDING;
+    for (text, size, weight) in lines {
+        text_y += size * 1.3;
+        let _ = writeln!(
+            label,
+            r#"<text x="{text_x:.2}" y="{text_y:.2}" font-family="sans-serif" font-size="{size:.2}" font-weight="{weight}">{}</text>"#,
+            escape_xml(&text)
+        );
+    }
+
+    label.push_str("</g>\n");
+    Ok(label)
+}
+
+/// Render a QR code of `data` as an SVG path, `size` millimetres wide.
+fn render_svg_qr(data: &str, x: f64, y: f64, size: f64) -> Result<String, &'static str> {
+    let code = QrCode::new(data).map_err(|_| "Could not create QR code.")?;
+    let width = code.width();
+    // Leave a quiet zone of one module around the code.
+    let module = size / (width + 2) as f64;
+
+    let mut path = String::new();
+    for (i, color) in code.to_colors().iter().enumerate() {
+        if *color == Color::Dark {
+            let _ = write!(path, "M{} {}h1v1h-1z", i % width + 1, i / width + 1);
+        }
+    }
+
+    Ok(format!(
+        "<path transform=\"translate({x:.2} {y:.2}) scale({module:.4})\" d=\"{path}\" fill=\"black\"/>\n"
+    ))
+}
+
+/// Escape text so it can go inside an XML element.
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::jdnumber::{JdNumber, Location};
+    use crate::labels::{escape_xml, render, LabelFormat};
+
+    fn sample_jd(id: u32, label: &str) -> JdNumber {
+        JdNumber::new(
+            "_finance",
+            "_payroll",
+            12,
+            id,
+            None,
+            None,
+            label.to_string(),
+            PathBuf::new(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let mut jd_1 = sample_jd(1, "_sept_payroll");
+        jd_1.locations.push(Location::Physical {
+            description: "Box 3".to_string(),
+        });
+        let jd_2 = sample_jd(2, "_oct_&_nov");
+
+        let svg = render(&[&jd_1, &jd_2], LabelFormat::Svg).unwrap();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+        assert!(svg.contains(">12.01_sept_payroll</text>"));
+        assert!(svg.contains(">12_payroll</text>"));
+        assert!(svg.contains(">10-19_finance</text>"));
+        assert!(svg.contains(">Box 3</text>"));
+        assert!(svg.contains(">12.02_oct_&amp;_nov</text>"));
+        // one QR code per label
+        assert_eq!(svg.matches("<path").count(), 2);
+        assert!(svg.contains(r#"height="297mm""#));
+    }
+
+    #[test]
+    fn test_render_svg_pages() {
+        let ids: Vec<JdNumber> = (1..=15).map(|i| sample_jd(i, "_label")).collect();
+        let refs: Vec<&JdNumber> = ids.iter().collect();
+
+        let svg = render(&refs, LabelFormat::Svg).unwrap();
+        assert!(svg.contains(r#"height="594mm""#));
+        assert_eq!(svg.matches("<path").count(), 15);
+    }
+
+    #[test]
+    fn test_label_format() {
+        assert_eq!("svg".parse::<LabelFormat>(), Ok(LabelFormat::Svg));
+        assert!("pdf".parse::<LabelFormat>().is_err());
+    }
+
+    #[test]
+    fn test_escape_xml() {
+        assert_eq!(escape_xml("a<b>&\"c\""), "a&lt;b&gt;&amp;&quot;c&quot;");
+    }
+}
@@ -0,0 +1,657 @@
+use crate::jdignore::IgnoreRules;
+use crate::jdnumber::{Layout, AREA_EX, CATEGORY_EX, JD_EX, PROJECT_AREA_EX, PROJECT_EX};
+use crate::system::System;
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+/// How bad a lint finding is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something that is probably fine, but worth knowing.
+    Info,
+    /// Something that does not break the system, but should be tidied up.
+    Warning,
+    /// Something that breaks the system, like two ids with the same number.
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Severity::Info => write!(f, "info"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Error => write!(f, "error"),
+        }
+    }
+}
+
+/// The kind of problem a lint finding is about.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FindingKind {
+    /// More than one folder or file has the same number.
+    DuplicateNumber,
+    /// A category is filed under an area whose range does not contain it.
+    WrongArea,
+    /// An id is filed under a category other than the one its number names.
+    WrongCategory,
+    /// A file is sitting at area or category level.
+    StrayFile,
+    /// An area's range is not `x0-x9`.
+    BadAreaRange,
+    /// A name is not valid UTF-8.
+    NonUtf8Name,
+    /// An id's number is not padded, like `1.3` instead of `01.03`.
+    UnpaddedNumber,
+    /// The separator between an id's number and label is not `_`.
+    Separator,
+}
+
+/// A problem found in a Johnny Decimal tree.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Finding {
+    pub severity: Severity,
+    pub kind: FindingKind,
+    /// The file or folder the problem is about.
+    pub path: PathBuf,
+    /// A description of the problem.
+    pub message: String,
+}
+
+impl Finding {
+    fn new(kind: FindingKind, path: &Path, message: String) -> Self {
+        let severity = match kind {
+            FindingKind::Separator => Severity::Info,
+            FindingKind::StrayFile | FindingKind::NonUtf8Name | FindingKind::UnpaddedNumber => {
+                Severity::Warning
+            }
+            _ => Severity::Error,
+        };
+        Finding {
+            severity,
+            kind,
+            path: path.to_path_buf(),
+            message,
+        }
+    }
+}
+
+/// A fix for one or more lint findings: moving or renaming a file or folder.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Fix {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    /// Why the move is needed.
+    pub reasons: Vec<String>,
+}
+
+/// The name of an id, like `12.01_label`, taken apart.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct IdName {
+    project: Option<u32>,
+    category: u32,
+    id: u32,
+    /// If the id is three digits, because its category is extended.
+    extended: bool,
+    /// The number as it is written, like `1.3`.
+    written: String,
+    /// Everything after the number, like `_label`.
+    label: String,
+}
+
+impl IdName {
+    /// Get the project, category and id, for sorting and comparing.
+    fn key(&self) -> (Option<u32>, u32, u32) {
+        (self.project, self.category, self.id)
+    }
+
+    /// Get the padded number with a different id.
+    fn number(&self, id: u32) -> String {
+        let width = if self.extended { 3 } else { 2 };
+        match self.project {
+            Some(project) => format!(
+                "{:0>3}.{:0>2}.{:0>width$}",
+                project,
+                self.category,
+                id,
+                width = width
+            ),
+            None => format!("{:0>2}.{:0>width$}", self.category, id, width = width),
+        }
+    }
+
+    /// Get the label with `_` as the separator.
+    fn normal_label(&self) -> String {
+        format!("_{}", self.label.trim_start_matches([' ', '_', '-']))
+    }
+}
+
+/// What a single file or folder name is in a Johnny Decimal system.
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Component {
+    /// `100-199_projects`
+    ProjectArea,
+    /// `101_project`
+    Project(u32),
+    /// `10-19_area`, with the first and last number of the range.
+    Area(u32, u32),
+    /// `12_category`
+    Category(u32),
+    /// `12.01_id` or `101.12.01_id`
+    Id(IdName),
+    /// Anything else.
+    Other,
+}
+
+/// Ids that are missing padding, like `1.3_label`.
+static LOOSE_ID_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^()(\d\d?)\.(\d\d?)(\D.*)$").expect("Hardcoded regex is valid."));
+
+/// Work out what a name is.
+fn classify(name: &str) -> Component {
+    if PROJECT_AREA_EX.is_match(name) {
+        return Component::ProjectArea;
+    }
+    if let Some(caps) = AREA_EX.captures(name) {
+        return Component::Area(caps[1].parse().unwrap(), caps[2].parse().unwrap());
+    }
+    if let Some(caps) = JD_EX.captures(name).or_else(|| LOOSE_ID_EX.captures(name)) {
+        let label = caps[4].to_string();
+        return Component::Id(IdName {
+            project: caps.get(1).and_then(|v| v.as_str().parse().ok()),
+            category: caps[2].parse().unwrap(),
+            id: caps[3].parse().unwrap(),
+            extended: caps[3].len() == 3,
+            written: name[..name.len() - label.len()].to_string(),
+            label,
+        });
+    }
+    if let Some(caps) = PROJECT_EX.captures(name) {
+        return Component::Project(caps[1].parse().unwrap());
+    }
+    if let Some(caps) = CATEGORY_EX.captures(name) {
+        return Component::Category(caps[1].parse().unwrap());
+    }
+    Component::Other
+}
+
+/// An id found while walking the tree.
+struct IdEntry {
+    path: PathBuf,
+    name: IdName,
+    /// Where the id should be moved to, if it is filed in the wrong place.
+    /// `Some(None)` means it is filed wrong but there is nowhere to move it.
+    refile: Option<Option<PathBuf>>,
+}
+
+/// Walks a tree and collects findings.
+struct Linter {
+    layout: Layout,
+    findings: Vec<Finding>,
+    /// Every id seen, in the order they were found.
+    ids: Vec<IdEntry>,
+    /// Every area folder, keyed by project and the first number of the area.
+    areas: BTreeMap<(Option<u32>, u32), PathBuf>,
+    /// Every category folder, keyed by project and category.
+    categories: BTreeMap<(Option<u32>, u32), PathBuf>,
+    /// What to leave out of the walk.
+    rules: IgnoreRules,
+}
+
+impl Linter {
+    /// Walk the tree at `root`, collecting findings and ids.
+    fn walk(root: &Path, layout: Layout) -> Self {
+        let mut linter = Linter {
+            layout,
+            findings: Vec::new(),
+            ids: Vec::new(),
+            areas: BTreeMap::new(),
+            categories: BTreeMap::new(),
+            rules: IgnoreRules::new(root, false),
+        };
+        linter.lint_folder(root, &Component::Other, None);
+
+        // Once one id of a category is three digits, they all should be.
+        let extended: BTreeSet<(Option<u32>, u32)> = linter
+            .ids
+            .iter()
+            .filter(|e| e.name.extended)
+            .map(|e| (e.name.project, e.name.category))
+            .collect();
+        for entry in &mut linter.ids {
+            entry.name.extended = extended.contains(&(entry.name.project, entry.name.category));
+            let number = entry.name.number(entry.name.id);
+            if entry.name.written != number {
+                linter.findings.push(Finding::new(
+                    FindingKind::UnpaddedNumber,
+                    &entry.path,
+                    format!("{} should be written {}.", entry.name.written, number),
+                ));
+            }
+        }
+
+        // Now that every folder is known, work out where misfiled ids should go.
+        for entry in &mut linter.ids {
+            if let Some(refile) = &mut entry.refile {
+                let key = (entry.name.project, entry.name.category);
+                *refile = if layout.has_categories() {
+                    linter.categories.get(&key).cloned()
+                } else {
+                    let area = (entry.name.project, entry.name.category / 10 * 10);
+                    linter.areas.get(&area).cloned()
+                };
+            }
+        }
+
+        linter
+    }
+
+    /// Group the ids that share a number.
+    fn duplicates(&self) -> Vec<Vec<&IdEntry>> {
+        let mut by_number: BTreeMap<(Option<u32>, u32, u32), Vec<&IdEntry>> = BTreeMap::new();
+        for entry in &self.ids {
+            by_number.entry(entry.name.key()).or_default().push(entry);
+        }
+        by_number
+            .into_values()
+            .filter(|entries| entries.len() > 1)
+            .collect()
+    }
+
+    /// Lint everything inside `folder`, which is a `parent` in `project`.
+    fn lint_folder(&mut self, folder: &Path, parent: &Component, project: Option<u32>) {
+        let mut entries: Vec<fs::DirEntry> = match fs::read_dir(folder) {
+            Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+            Err(_) => return,
+        };
+        entries.sort_by_key(|e| e.file_name());
+
+        for entry in entries {
+            let path = entry.path();
+            let is_dir = entry.file_type().map(|t| t.is_dir()).unwrap_or(false);
+            if self.rules.is_ignored(&path, is_dir) {
+                continue;
+            }
+
+            let file_name = entry.file_name();
+            let name = match file_name.to_str() {
+                Some(name) => name,
+                None => {
+                    self.findings.push(Finding::new(
+                        FindingKind::NonUtf8Name,
+                        &path,
+                        format!("\"{}\" is not valid UTF-8.", file_name.to_string_lossy()),
+                    ));
+                    continue;
+                }
+            };
+            let component = classify(name);
+            self.check(&path, name, is_dir, &component, parent);
+
+            // Do not look inside ids, or inside things that are not part of the system.
+            if !is_dir {
+                continue;
+            }
+            match component {
+                Component::ProjectArea => self.lint_folder(&path, &component, project),
+                Component::Project(number) => self.lint_folder(&path, &component, Some(number)),
+                Component::Area(start, _) => {
+                    self.areas.insert((project, start), path.clone());
+                    self.lint_folder(&path, &component, project);
+                }
+                Component::Category(category) => {
+                    self.categories.insert((project, category), path.clone());
+                    self.lint_folder(&path, &component, project);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Check a single file or folder.
+    fn check(
+        &mut self,
+        path: &Path,
+        name: &str,
+        is_dir: bool,
+        component: &Component,
+        parent: &Component,
+    ) {
+        match component {
+            Component::Area(start, end) if start % 10 != 0 || *end != start + 9 => {
+                self.findings.push(Finding::new(
+                    FindingKind::BadAreaRange,
+                    path,
+                    format!("Area range {:0>2}-{:0>2} is not x0-x9.", start, end),
+                ));
+            }
+            Component::Category(category) => {
+                if let Component::Area(start, end) = parent {
+                    if category < start || category > end {
+                        self.findings.push(Finding::new(
+                            FindingKind::WrongArea,
+                            path,
+                            format!(
+                                "Category {:0>2} is not in area {:0>2}-{:0>2}.",
+                                category, start, end
+                            ),
+                        ));
+                    }
+                }
+            }
+            Component::Id(id_name) => self.check_id(path, id_name, parent),
+            _ => {}
+        }
+
+        // Files at area level (the root) or category level (in an area)
+        // should not be there, unless they are ids in a layout without categories.
+        let at_area_level = matches!(parent, Component::Other | Component::Project(_));
+        let at_category_level = matches!(parent, Component::Area(_, _));
+        let is_id = matches!(component, Component::Id(_));
+        let id_allowed = !self.layout.has_categories() && at_category_level;
+        if !is_dir && (at_area_level || at_category_level) && !(is_id && id_allowed) {
+            let level = if at_area_level { "area" } else { "category" };
+            self.findings.push(Finding::new(
+                FindingKind::StrayFile,
+                path,
+                format!("\"{}\" is a file at {} level.", name, level),
+            ));
+        }
+    }
+
+    /// Check an id, and remember it for finding duplicates.
+    fn check_id(&mut self, path: &Path, name: &IdName, parent: &Component) {
+        let number = name.number(name.id);
+        let mut misfiled = false;
+
+        match parent {
+            Component::Category(parent_category) if *parent_category != name.category => {
+                misfiled = true;
+                self.findings.push(Finding::new(
+                    FindingKind::WrongCategory,
+                    path,
+                    format!("{} is filed in category {:0>2}.", number, parent_category),
+                ));
+            }
+            Component::Area(start, end) if name.category < *start || name.category > *end => {
+                misfiled = true;
+                self.findings.push(Finding::new(
+                    FindingKind::WrongArea,
+                    path,
+                    format!("{} is not in area {:0>2}-{:0>2}.", number, start, end),
+                ));
+            }
+            _ => {}
+        }
+
+        if name.label != name.normal_label() {
+            self.findings.push(Finding::new(
+                FindingKind::Separator,
+                path,
+                format!("{} should be separated from its label with _.", number),
+            ));
+        }
+
+        self.ids.push(IdEntry {
+            path: path.to_path_buf(),
+            name: name.clone(),
+            refile: if misfiled { Some(None) } else { None },
+        });
+    }
+}
+
+/// Check a Johnny Decimal tree for problems.
+///
+/// This walks the tree at `root`, which is laid out with `layout`,
+/// and returns everything it finds, most severe first.
+pub fn lint(root: &Path, layout: Layout) -> Vec<Finding> {
+    let mut linter = Linter::walk(root, layout);
+
+    let mut duplicates = Vec::new();
+    for entries in linter.duplicates() {
+        let number = entries[0].name.number(entries[0].name.id);
+        for entry in &entries {
+            duplicates.push(Finding::new(
+                FindingKind::DuplicateNumber,
+                &entry.path,
+                format!("{} is used {} times.", number, entries.len()),
+            ));
+        }
+    }
+    linter.findings.append(&mut duplicates);
+
+    // Most severe first; the sort is stable so the walk order is kept otherwise.
+    linter
+        .findings
+        .sort_by_key(|f| std::cmp::Reverse(f.severity));
+    linter.findings
+}
+
+/// Plan fixes for the problems in a Johnny Decimal tree.
+///
+/// Ids are moved into the category (or area) their number names,
+/// numbers are padded, separators are made `_`, and all but the first
+/// of a set of duplicates are renumbered into free ids.  Numbers used
+/// in `system` are never given out as free.
+///
+/// Problems that cannot be fixed by moving an id, like a misfiled id
+/// whose category has no folder, are left alone.
+pub fn plan_fixes(root: &Path, layout: Layout, system: &System) -> Vec<Fix> {
+    let linter = Linter::walk(root, layout);
+
+    // Every number that is in use, on disk or in the index.
+    let mut taken: BTreeSet<(Option<u32>, u32, u32)> =
+        linter.ids.iter().map(|e| e.name.key()).collect();
+    taken.extend(system.id.iter().map(|jd| (jd.project, jd.category, jd.id)));
+
+    // Give every duplicate but the first a new id.
+    let mut new_ids: BTreeMap<&Path, u32> = BTreeMap::new();
+    for entries in linter.duplicates() {
+        for entry in &entries[1..] {
+            let (project, category, _) = entry.name.key();
+            let max = if entry.name.extended { 999 } else { 99 };
+            if let Some(free) = (1..=max).find(|id| !taken.contains(&(project, category, *id))) {
+                taken.insert((project, category, free));
+                new_ids.insert(&entry.path, free);
+            }
+        }
+    }
+
+    let mut fixes = Vec::new();
+    for entry in &linter.ids {
+        let mut reasons = Vec::new();
+
+        let folder = match &entry.refile {
+            Some(Some(folder)) => {
+                reasons.push(String::from("filed in the wrong place"));
+                folder.clone()
+            }
+            _ => match entry.path.parent() {
+                Some(parent) => parent.to_path_buf(),
+                None => continue,
+            },
+        };
+
+        let id = match new_ids.get(entry.path.as_path()) {
+            Some(id) => {
+                reasons.push(format!(
+                    "{} is a duplicate",
+                    entry.name.number(entry.name.id)
+                ));
+                *id
+            }
+            None => entry.name.id,
+        };
+
+        if entry.name.written != entry.name.number(entry.name.id) {
+            reasons.push(String::from("number is not padded"));
+        }
+        if entry.name.label != entry.name.normal_label() {
+            reasons.push(String::from("separator is not _"));
+        }
+
+        let to = folder.join(format!(
+            "{}{}",
+            entry.name.number(id),
+            entry.name.normal_label()
+        ));
+        if to != entry.path {
+            fixes.push(Fix {
+                from: entry.path.clone(),
+                to,
+                reasons,
+            });
+        }
+    }
+
+    fixes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::testing::TempRoot;
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::jdnumber::Layout;
+    use crate::lint::{lint, plan_fixes, FindingKind, Severity};
+    use crate::system::System;
+
+    #[test]
+    fn test_clean_tree() {
+        let root = TempRoot::new("lint_clean");
+        fs::create_dir_all(root.join("10-19_finance/12_payroll/12.01_sept_payroll")).unwrap();
+        fs::write(root.join("10-19_finance/12_payroll/12.02_invoice.pdf"), "").unwrap();
+        fs::write(root.join(".JdIndex"), "").unwrap();
+
+        assert_eq!(lint(&root, Layout::Full), Vec::new());
+    }
+
+    #[test]
+    fn test_findings() {
+        let root = TempRoot::new("lint_findings");
+        fs::create_dir_all(root.join("10-19_finance/12_payroll/12.01_sept_payroll")).unwrap();
+        fs::create_dir_all(root.join("10-19_finance/12_payroll/13.01_misfiled")).unwrap();
+        fs::create_dir_all(root.join("10-19_finance/22_contracts")).unwrap();
+        fs::create_dir_all(root.join("20-29_admin/12_old_payroll/12.01_copy")).unwrap();
+        fs::create_dir_all(root.join("25-34_bad")).unwrap();
+        fs::write(root.join("notes.txt"), "").unwrap();
+        fs::write(root.join("10-19_finance/todo.txt"), "").unwrap();
+
+        let findings = lint(&root, Layout::Full);
+        let kinds = |kind: FindingKind| findings.iter().filter(|f| f.kind == kind).count();
+
+        assert_eq!(kinds(FindingKind::DuplicateNumber), 2);
+        // 22 in 10-19, and 12 in 20-29
+        assert_eq!(kinds(FindingKind::WrongArea), 2);
+        assert_eq!(kinds(FindingKind::WrongCategory), 1);
+        assert_eq!(kinds(FindingKind::BadAreaRange), 1);
+        assert_eq!(kinds(FindingKind::StrayFile), 2);
+        assert_eq!(findings.len(), 8);
+
+        // errors come first
+        assert_eq!(findings[0].severity, Severity::Error);
+        assert_eq!(findings[7].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_padding_and_separators() {
+        let root = TempRoot::new("lint_padding");
+        fs::create_dir_all(root.join("10-19_finance/12_payroll/12.1_sept_payroll")).unwrap();
+        fs::create_dir_all(root.join("10-19_finance/12_payroll/12.02 oct payroll")).unwrap();
+
+        let findings = lint(&root, Layout::Full);
+        assert_eq!(findings.len(), 2);
+        assert_eq!(findings[0].kind, FindingKind::UnpaddedNumber);
+        assert_eq!(findings[1].kind, FindingKind::Separator);
+        assert_eq!(findings[1].severity, Severity::Info);
+    }
+
+    #[test]
+    fn test_extended_category() {
+        let root = TempRoot::new("lint_extended");
+        let payroll = root.join("10-19_finance/12_payroll");
+        fs::create_dir_all(payroll.join("12.001_sept_payroll")).unwrap();
+        fs::create_dir_all(payroll.join("12.02_oct_payroll")).unwrap();
+        fs::create_dir_all(payroll.join("12.120_nov_payroll")).unwrap();
+        fs::create_dir_all(root.join("10-19_finance/13_bookkeeping/13.01_ledger")).unwrap();
+
+        let findings = lint(&root, Layout::Full);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::UnpaddedNumber);
+        assert_eq!(findings[0].message, "12.02 should be written 12.002.");
+
+        let fixes = plan_fixes(&root, Layout::Full, &System::new(root.to_path_buf()));
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].to, payroll.join("12.002_oct_payroll"));
+    }
+
+    #[test]
+    fn test_plan_fixes() {
+        let root = TempRoot::new("lint_fixes");
+        let payroll = root.join("10-19_finance/12_payroll");
+        let bookkeeping = root.join("10-19_finance/13_bookkeeping");
+        fs::create_dir_all(payroll.join("12.01_sept_payroll")).unwrap();
+        fs::create_dir_all(payroll.join("12.01_copy")).unwrap();
+        fs::create_dir_all(payroll.join("12.3_oct_payroll")).unwrap();
+        fs::create_dir_all(payroll.join("13.01 ledger")).unwrap();
+        fs::create_dir_all(&bookkeeping).unwrap();
+
+        // 12.02 is in the index but not on disk, so it is not free.
+        let mut system = System::new(root.to_path_buf());
+        system
+            .add_id(crate::jdnumber::JdNumber::try_from(String::from("12.02")).unwrap())
+            .unwrap();
+
+        let fixes = plan_fixes(&root, Layout::Full, &system);
+        let moves: Vec<(PathBuf, PathBuf)> = fixes.into_iter().map(|f| (f.from, f.to)).collect();
+
+        assert_eq!(
+            moves,
+            vec![
+                (
+                    payroll.join("12.01_sept_payroll"),
+                    payroll.join("12.04_sept_payroll")
+                ),
+                (
+                    payroll.join("12.3_oct_payroll"),
+                    payroll.join("12.03_oct_payroll")
+                ),
+                (
+                    payroll.join("13.01 ledger"),
+                    bookkeeping.join("13.01_ledger")
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_category_only_layout() {
+        let root = TempRoot::new("lint_category_only");
+        fs::create_dir_all(root.join("10-19_finance/12.01_sept_payroll")).unwrap();
+        fs::write(root.join("10-19_finance/12.02_invoice.pdf"), "").unwrap();
+        fs::create_dir_all(root.join("10-19_finance/22.01_misfiled")).unwrap();
+
+        let findings = lint(&root, Layout::CategoryOnly);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::WrongArea);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_name() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let root = TempRoot::new("lint_non_utf8");
+        let name = OsStr::from_bytes(b"12.01_caf\xe9");
+        fs::create_dir_all(root.join("10-19_finance/12_payroll").join(name)).unwrap();
+
+        let findings = lint(&root, Layout::Full);
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].kind, FindingKind::NonUtf8Name);
+        assert_eq!(findings[0].severity, Severity::Warning);
+    }
+}
@@ -2,15 +2,40 @@ use clap::Parser;
 use colored::Colorize;
 use libc;
 use regex::Regex;
+use std::io::{self, Write};
 use std::path::PathBuf;
-use std::{env, fs, path};
-use walkdir::{DirEntry, WalkDir};
+use std::process::Command;
+use std::time::{Duration, SystemTime};
+use std::{fs, path};
 
+pub mod jdignore;
 pub mod jdnumber;
+pub mod jump;
+pub mod labels;
+pub mod lint;
+pub mod merge;
+pub mod prompt;
+pub mod scan;
+pub mod shell;
+pub mod stats;
+pub mod storage;
 pub mod system;
+#[cfg(test)]
+mod testing;
+pub mod usage;
 
-use jdnumber::JdNumber;
-use system::System;
+use jdnumber::{JdNumber, Layout, Location};
+use jump::Target;
+use labels::LabelFormat;
+use lint::Severity;
+use merge::{Base, Resolution, Side};
+use scan::ScanOptions;
+use shell::InitShell;
+use stats::Stats;
+use storage::registry::{self, Registry};
+use storage::{Backend, IndexLock};
+use system::{Depth, System, TreeOptions};
+use usage::SortBy;
 
 #[derive(Parser)]
 struct Cli {
@@ -21,9 +46,44 @@ struct Cli {
 #[derive(clap::Subcommand)]
 enum Subcommand {
     /// Index an existing Johnny Decimal system
+    #[clap(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
     Index {
-        #[clap(parse(from_os_str))]
-        path: path::PathBuf,
+        #[clap(subcommand)]
+        action: Option<IndexAction>,
+        #[clap(parse(from_os_str), required = true)]
+        path: Option<path::PathBuf>,
+        /// How the system is laid out: full, no-area or category-only.
+        ///
+        /// Defaults to the layout of the existing index, or full.
+        #[clap(long)]
+        layout: Option<Layout>,
+        /// How the index is stored: ron, binary or sqlite.
+        ///
+        /// Defaults to the backend of the existing index, or ron.
+        #[clap(long)]
+        backend: Option<Backend>,
+        /// Keep the index in `$XDG_DATA_HOME/jd` instead of in the root folder.
+        ///
+        /// Once a system's index is kept there, it stays there when indexing again.
+        #[clap(long)]
+        external: bool,
+        /// Move an index kept in `$XDG_DATA_HOME/jd` back into the root folder.
+        #[clap(long, conflicts_with = "external")]
+        in_tree: bool,
+        /// Also index hidden files and folders, whose names start with `.`.
+        #[clap(long)]
+        include_hidden: bool,
+        /// Also look for JD numbers inside ID folders.
+        ///
+        /// What is inside an ID folder belongs to that ID, so indexing
+        /// normally stops there.
+        #[clap(long)]
+        inside_ids: bool,
+    },
+    /// Convert the index of the system you are in to another backend
+    MigrateIndex {
+        /// The backend to use: ron, binary or sqlite.
+        backend: Backend,
     },
     /// Show part or all of a Johnny Decimal system
     #[clap(visible_alias("display"))]
@@ -34,23 +94,120 @@ enum Subcommand {
         /// - PRO.AC.ID or AC.ID
         /// - PRO
         /// AC or PRO.AC
+        /// - AC-AC or PRO.AC-AC, an area like 10-19
         ///
         /// If this is not given, or something other than acceptable values is given,
         /// the whole Johnny Decimal system is shown.
         item: Option<String>,
+        /// List every location of each number.
+        #[clap(long)]
+        locations: bool,
+        /// How deep to show: area, category or id.
+        #[clap(long, default_value = "id")]
+        depth: Depth,
+        /// List the files and folders inside each ID folder.
+        #[clap(long)]
+        files: bool,
     },
     /// Get the path for a Johnny Decimal number
+    ///
+    /// Categories like `12` and areas like `10-19` give the path of their
+    /// folder.  Anything else is searched for in the labels, so
+    /// `jd path payroll oct` finds `12.02_oct_payroll`; if more than one
+    /// label matches, you are asked which one.
     Path {
+        /// The Johnny Decimal number, category, area or words of a label.
+        #[clap(required = true)]
+        item: Vec<String>,
+        /// If the number is a file, print the folder containing it instead.
+        #[clap(long)]
+        dir: bool,
+    },
+    /// Open a Johnny Decimal number with the default application
+    Open {
         /// The Johnny Decimal number.
         item: String,
     },
     /// List all Johnny Decimal numbers, one each line.
     #[clap(visible_alias("ls"))]
     List,
+    /// Show the area, category and ID a path is in
+    ///
+    /// The path can be anything inside the system, like a file in an ID
+    /// folder.  Exits with an error if it is not in a system.
+    Which {
+        #[clap(parse(from_os_str))]
+        path: PathBuf,
+    },
+    /// Show the area, category and ID the current directory is in
+    Here,
+    /// Print the ID the current directory is in, for a shell prompt
+    ///
+    /// Prints nothing outside an ID folder.  This only looks at the names
+    /// of the folders above the current directory, and does not read the
+    /// index, so it is fast enough to run for every prompt.
+    Prompt {
+        /// What to print.  {number}, {label}, {category} and {area} are filled in.
+        #[clap(long, default_value = prompt::DEFAULT_FORMAT)]
+        format: String,
+    },
     /// [Shell config only] Install shell bindings.
     Init { shell: InitShell },
-    /// (Not working) Search for a a Johnny Decimal number
-    Search { term: Option<String> },
+    /// [Shell config only] List the Johnny Decimal numbers starting with some text
+    ///
+    /// Each number is printed with its label after a tab.  This is used by
+    /// the tab completion that `init` sets up.
+    Complete {
+        /// The start of a Johnny Decimal number.
+        #[clap(default_value = "")]
+        partial: String,
+    },
+    /// Search for Johnny Decimal numbers by the words in their labels
+    ///
+    /// Every word of the search has to start a word of the label, so
+    /// `jd search pay oct` finds `12.02_oct_payroll`.
+    Search { term: String },
+    /// Make a sheet of printable labels for physical filing
+    Labels {
+        /// The numbers to make labels for.
+        ///
+        /// This can be part or all of a Johnny Decimal number, like for `show`.
+        /// If it is not given, labels are made for the whole system.
+        item: Option<String>,
+        /// The format of the labels.
+        #[clap(long, default_value = "svg")]
+        format: LabelFormat,
+        /// Only make labels for numbers that have a physical location.
+        #[clap(long)]
+        physical: bool,
+        /// Write the labels to this file instead of printing them.
+        #[clap(short, long, parse(from_os_str))]
+        output: Option<path::PathBuf>,
+    },
+    /// Check a Johnny Decimal tree for problems
+    Lint {
+        /// The root folder to check.
+        ///
+        /// Defaults to the root of the system you are in.
+        #[clap(parse(from_os_str))]
+        path: Option<path::PathBuf>,
+        /// How the tree is laid out: full, no-area or category-only.
+        ///
+        /// Defaults to the layout of the system you are in, or full.
+        #[clap(long)]
+        layout: Option<Layout>,
+        /// Fix what can be fixed by moving and renaming ids.
+        ///
+        /// This only works on the system you are in.
+        #[clap(long, conflicts_with = "path")]
+        fix: bool,
+        /// Show the fixes without making them.
+        #[clap(long, requires = "fix")]
+        dry_run: bool,
+        /// Ask before making each fix.
+        #[clap(long, requires = "fix", conflicts_with = "dry-run")]
+        confirm: bool,
+    },
     /// Add a Johnny Decimal number to the system
     Add {
         /// The category to add the number to
@@ -58,51 +215,146 @@ enum Subcommand {
         /// The title of the number
         title: String,
     },
+    /// Show how full each area and category is
+    ///
+    /// This lists used and free numbers, the largest gaps between used ids,
+    /// the categories that grew the most recently and how many ids are
+    /// archived.  An id is archived if a word of its label, or of its
+    /// category or area label, starts with "archive".
+    Stats {
+        /// Warn about categories and areas that are at least this full, in percent.
+        #[clap(long, default_value = "80")]
+        threshold: u32,
+        /// Count ids added in this many days towards growth.
+        #[clap(long, default_value = "90")]
+        days: u64,
+        /// Print the stats as JSON.
+        #[clap(long)]
+        json: bool,
+    },
+    /// Show how much is on disk under each area, category and id
+    ///
+    /// Totals are cached in the index, and only counted again when a
+    /// folder inside the id has changed.  Editing a file does not change
+    /// its folder, so use --refresh to count everything again.
+    Du {
+        /// The part of the system to show, like 12.01, 12, 10-19 or 101.
+        ///
+        /// If this is not given, the whole system is shown.
+        item: Option<String>,
+        /// How to sort each level: number, size or files.
+        #[clap(long, default_value = "number")]
+        sort: SortBy,
+        /// Count everything again instead of using cached totals.
+        #[clap(long)]
+        refresh: bool,
+    },
+    /// Give the ids of a category three digits, like 12.001
+    ///
+    /// This lets a category hold up to 999 ids.  The folders and files of
+    /// its ids are renamed to match, so `12.05_x` becomes `12.005_x`.
+    Extend {
+        /// The category to extend, like 12 or 101.12
+        category: String,
+        /// Show what would be renamed without renaming it.
+        #[clap(long)]
+        dry_run: bool,
+    },
 }
 
-#[derive(Debug, Parser)]
-enum InitShell {
-    Bash,
-    Elvish,
-    Fish,
-    Nushell,
-    Posix,
-    Powershell,
-    Xonsh,
-    Zsh,
-}
-
-impl std::str::FromStr for InitShell {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "bash" => Ok(InitShell::Bash),
-            "elvish" => Ok(InitShell::Elvish),
-            "fish" => Ok(InitShell::Fish),
-            "nushell" => Ok(InitShell::Nushell),
-            "posix" => Ok(InitShell::Posix),
-            "powershell" => Ok(InitShell::Powershell),
-            "xonsh" => Ok(InitShell::Xonsh),
-            "zsh" => Ok(InitShell::Zsh),
-            _ => Err(String::from("unknown shell.")),
-        }
-    }
+#[derive(clap::Subcommand)]
+enum IndexAction {
+    /// Merge two copies of an index, like a sync conflict copy and the original
+    Merge {
+        /// The first index.  The merged index is written over it.
+        #[clap(parse(from_os_str))]
+        a: PathBuf,
+        /// The second index.
+        #[clap(parse(from_os_str))]
+        b: PathBuf,
+        /// An older index that both were changed from.
+        ///
+        /// Defaults to the system as it is on disk.
+        #[clap(long, parse(from_os_str))]
+        base: Option<PathBuf>,
+        /// Settle collisions by giving the numbers from this side (a or b) new ids,
+        /// instead of asking.
+        #[clap(long)]
+        renumber: Option<Side>,
+        /// Write the merged index here instead of over the first index.
+        #[clap(short, long, parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
 }
 
 fn main() -> Result<(), ()> {
     let cli = Cli::parse();
 
     match cli.subcommand {
-        Subcommand::Index { path } => {
-            index(path);
+        Subcommand::Index {
+            action:
+                Some(IndexAction::Merge {
+                    a,
+                    b,
+                    base,
+                    renumber,
+                    output,
+                }),
+            ..
+        } => merge_indexes(a, b, base, renumber, output)?,
+        Subcommand::Index {
+            action: None,
+            path: Some(path),
+            layout,
+            backend,
+            external,
+            in_tree,
+            include_hidden,
+            inside_ids,
+        } => {
+            let external = match (external, in_tree) {
+                (true, _) => Some(true),
+                (_, true) => Some(false),
+                _ => None,
+            };
+            index(path, layout, backend, external, include_hidden, inside_ids);
+        }
+        Subcommand::Index { .. } => unreachable!("clap requires a path or an action"),
+        Subcommand::MigrateIndex { backend } => {
+            let (lock, system) = print_error(lock_system())?;
+            print_error(lock.write_as(&system, backend))?;
+            println!(
+                "{} {} to the {} backend.",
+                "Converted".green(),
+                lock.path.display(),
+                backend
+            );
         }
-        Subcommand::Show { item: term } => {
+        Subcommand::Show {
+            item: term,
+            locations,
+            depth,
+            files,
+        } => {
             let system = print_error(get_system())?;
-            let output = print_error(system.display(term))?;
-            println!("{}", output);
+            let options = TreeOptions {
+                depth,
+                locations,
+                files,
+                // off when piped, or when NO_COLOR is set
+                color: colored::control::SHOULD_COLORIZE.should_colorize(),
+            };
+            let output = print_error(system.tree(term, options))?;
+            print!("{}", output);
         }
-        Subcommand::Path { item: term } => match go_to_jd(term) {
+        Subcommand::Path { item: term, dir } => {
+            // Errors go to stderr and fail, so `j` does not try to cd to them.
+            if let Err(message) = go_to_jd(term.join(" "), dir) {
+                eprintln!("{} {}", "Error:".magenta(), message);
+                std::process::exit(1);
+            }
+        }
+        Subcommand::Open { item: term } => match open_jd(term) {
             Ok(_) => {}
             Err(message) => println!("{} {}", "Error:".magenta(), message),
         },
@@ -114,19 +366,179 @@ fn main() -> Result<(), ()> {
             }
         }
         Subcommand::Init { shell } => init(shell),
+        Subcommand::Complete { partial } => {
+            // Errors would be taken as numbers, so outside a system there is nothing.
+            if let Ok(system) = get_system() {
+                for candidate in shell::complete(&system, &partial) {
+                    println!("{}", candidate);
+                }
+            }
+        }
+        Subcommand::Which { path } => print_error(which(&path))?,
+        Subcommand::Prompt { format } => {
+            let found = std::env::current_dir().ok().and_then(|dir| {
+                let (root, _) = storage::find_system_for(&dir)?;
+                prompt::find(&root, &dir)
+            });
+            if let Some(jd) = found {
+                println!("{}", prompt::render(&format, &jd));
+            }
+        }
+        Subcommand::Here => print_error(
+            std::env::current_dir()
+                .map_err(|_| String::from("Cannot read the current directory."))
+                .and_then(|dir| which(&dir)),
+        )?,
         Subcommand::Search { term } => {
-            match term {
-                Some(_x) => {}
-                None => {}
+            let system = print_error(get_system())?;
+            let found = system.search_labels(&term);
+            if found.is_empty() {
+                return print_error(Err("No JD numbers match."));
+            }
+            for jd_number in found {
+                println!("{}", jd_number);
+            }
+        }
+        Subcommand::Labels {
+            item,
+            format,
+            physical,
+            output,
+        } => {
+            let system = print_error(get_system())?;
+            let mut ids = print_error(system.select(item))?;
+
+            if physical {
+                ids.retain(|jd| {
+                    jd.all_locations()
+                        .any(|l| matches!(l, Location::Physical { .. }))
+                });
+            }
+
+            let text = print_error(labels::render(&ids, format))?;
+            match output {
+                Some(path) => {
+                    print_error(fs::write(path, text).map_err(|_| "Cannot write to file."))?
+                }
+                None => print!("{}", text),
+            }
+        }
+        Subcommand::Lint {
+            path,
+            layout,
+            fix,
+            dry_run,
+            confirm,
+        } => {
+            let system = get_system().ok();
+            let root = match (path, &system) {
+                (Some(path), _) => path,
+                (None, Some(system)) => system.path.clone(),
+                (None, None) => {
+                    return print_error(Err("Not in a valid Johnny Decimal system"));
+                }
             };
-            println!("{}: Search does not work yet.", "Error".red());
+            let layout = layout
+                .or_else(|| system.as_ref().map(|s| s.layout))
+                .unwrap_or_default();
+
+            if fix {
+                let (lock, system) = print_error(lock_system())?;
+                fix_lints(&lock, system, layout, dry_run, confirm)?;
+            }
+
+            let findings = lint::lint(&root, layout);
+            for finding in &findings {
+                let severity = match finding.severity {
+                    Severity::Error => finding.severity.to_string().red(),
+                    Severity::Warning => finding.severity.to_string().yellow(),
+                    Severity::Info => finding.severity.to_string().blue(),
+                };
+                println!(
+                    "{}: {}\n    {}",
+                    severity,
+                    finding.message,
+                    finding.path.display()
+                );
+            }
+
+            if findings.is_empty() {
+                println!("{} No problems found.", "Ok:".green());
+            }
+            if findings.iter().any(|f| f.severity == Severity::Error) {
+                return Err(());
+            }
         }
         Subcommand::Add { category, title } => {
-            let mut system = print_error(get_system())?;
+            let (lock, mut system) = print_error(lock_system())?;
 
             print_error(system.add_id_from_str(category, title))?;
 
-            print_error(write_index(system))?;
+            print_error(lock.write(&system))?;
+        }
+        Subcommand::Stats {
+            threshold,
+            days,
+            json,
+        } => {
+            let system = print_error(get_system())?;
+            let since = SystemTime::now() - Duration::from_secs(days * 24 * 60 * 60);
+            let stats = Stats::new(&system, threshold, since);
+
+            if json {
+                println!("{}", print_error(serde_json::to_string_pretty(&stats))?);
+            } else {
+                print_stats(&stats, days);
+            }
+        }
+        Subcommand::Du {
+            item,
+            sort,
+            refresh,
+        } => {
+            let (lock, mut system) = print_error(lock_system())?;
+
+            let selected: Vec<JdNumber> = print_error(system.select(item))?
+                .into_iter()
+                .cloned()
+                .collect();
+            let selected: Vec<&JdNumber> = selected.iter().collect();
+            let (usages, counted) = usage::measure(&mut system, &selected, refresh);
+
+            let mut tree = usage::tree(&selected, &usages);
+            tree.sort(sort);
+            print_usage(&tree, 0);
+            println!(
+                "{:>10} {:>8} files  total",
+                usage::human_size(tree.value.bytes),
+                tree.value.files
+            );
+
+            if counted > 0 {
+                print_error(lock.write(&system))?;
+            }
+        }
+        Subcommand::Extend { category, dry_run } => {
+            let (lock, mut system) = print_error(lock_system())?;
+
+            let plan = print_error(system.plan_extend(category))?;
+            if plan.is_empty() {
+                println!("{} Nothing to extend.", "Ok:".green());
+                return Ok(());
+            }
+            for (from, to) in plan {
+                println!("{} {} -> {}", "Extend:".cyan(), from, to);
+                if dry_run {
+                    continue;
+                }
+                if let Err(message) = system.renumber(&from, to) {
+                    println!("{} {}", "Error:".magenta(), message);
+                }
+            }
+
+            if !dry_run {
+                print_error(lock.write(&system))?;
+            }
         }
     }
 
@@ -134,7 +546,7 @@ fn main() -> Result<(), ()> {
 }
 
 /// Print an error message
-fn print_error<T>(input: Result<T, &str>) -> Result<T, ()> {
+fn print_error<T, E: std::fmt::Display>(input: Result<T, E>) -> Result<T, ()> {
     match input {
         Ok(result) => Ok(result),
         Err(message) => {
@@ -145,26 +557,70 @@ fn print_error<T>(input: Result<T, &str>) -> Result<T, ()> {
 }
 
 /// Create an index for a johnnydecimal system
-fn index(mut filepath: path::PathBuf) {
+///
+/// `external` moves the index into (`Some(true)`) or out of (`Some(false)`)
+/// the data directory; otherwise it is written where it already is.
+///
+/// Anything matched by a `.jdignore`, and hidden files and folders unless
+/// `include_hidden` is set, are left out.  The walk stops at id folders
+/// unless `inside_ids` is set.
+fn index(
+    filepath: path::PathBuf,
+    layout: Option<Layout>,
+    backend: Option<Backend>,
+    external: Option<bool>,
+    include_hidden: bool,
+    inside_ids: bool,
+) {
     let mut system = System::new(filepath.clone().canonicalize().unwrap()); // create an empty JD system.
 
-    let walker = WalkDir::new(&filepath).into_iter(); // Create a new filewalker.
-    for entry in walker.filter_entry(|e| !is_hidden(e)) {
-        //Walk through every file and directory:
-
-        if entry.as_ref().unwrap().file_type().is_file() {
-            continue;
+    let (index_path, old_index) = match index_location(&system.path, external, backend) {
+        Ok(location) => location,
+        Err(message) => {
+            println!("{} {}", "Error:".magenta(), message);
+            return;
         }
+    };
+    let lock = match IndexLock::acquire(&index_path) {
+        Ok(lock) => lock,
+        Err(message) => {
+            println!("{} {}", "Error:".magenta(), message);
+            return;
+        }
+    };
 
-        let path = entry.as_ref().unwrap().path();
-        let jd_number: JdNumber = match JdNumber::try_from(PathBuf::from(path)) {
-            //check if it is a JD number,
-            Ok(number) => number,
-            Err(_err) => continue, //and if it is not, go to the next item.
-        };
+    // Keep the layout and labels of an existing index, because labels
+    // for folders that are not on disk cannot be found again.
+    let old_system = match &old_index {
+        Some(old_index) => storage::read_index(old_index),
+        None => lock.read(),
+    };
+    if let Ok(old_system) = old_system {
+        system.layout = old_system.layout;
+        system.area_labels = old_system.area_labels;
+        system.category_labels = old_system.category_labels;
+        system.usage = old_system.usage;
+    }
+    if let Some(layout) = layout {
+        system.layout = layout;
+    }
+
+    let options = ScanOptions {
+        layout: system.layout,
+        include_hidden,
+        inside_ids,
+    };
+    let found = scan::scan(&filepath, options);
+
+    for (path, reason) in &found.skipped {
+        println!("{} {} ({})", "Skipping:".yellow(), path.display(), reason);
+    }
+    for mut jd_number in found.ids {
+        system.fill_labels(&mut jd_number);
 
         println!("{} {}", "Indexing".green(), jd_number);
 
+        // The numbers are sorted, so this only ever adds to the end.
         match system.add_id(jd_number) {
             Ok(_) => {}
             Err(x) => {
@@ -173,45 +629,308 @@ fn index(mut filepath: path::PathBuf) {
         }
     }
 
-    filepath.push(".JdIndex");
-    fs::write(
-        &filepath,
-        ron::ser::to_string_pretty(&system, ron::ser::PrettyConfig::new()).unwrap(),
-    )
-    .expect("Could not write file");
+    let backend =
+        backend.unwrap_or_else(|| Backend::detect(old_index.as_ref().unwrap_or(&index_path)));
+    if let Err(message) = lock.write_as(&system, backend) {
+        println!("{} {}", "Error:".magenta(), message);
+        return;
+    }
 
-    println!("Index has been written to {}", filepath.display());
+    // The index has moved, so remove the old one.
+    if let Some(old_index) = old_index {
+        let mut lock_file = old_index.clone().into_os_string();
+        lock_file.push(".lock");
+        let _ = fs::remove_file(&old_index);
+        let _ = fs::remove_file(lock_file);
+    }
+
+    println!("Index has been written to {}", index_path.display());
 }
 
-fn init(shell: InitShell) {
-    // use the libc c interface to check if stdout is a tty or a pipe.
-    let istty = unsafe { libc::isatty(libc::STDOUT_FILENO as i32) } != 0;
+/// Work out where to write the index of the system at `root`.
+///
+/// Also returns the old index if the index is moving into or out of
+/// the data directory.
+fn index_location(
+    root: &path::Path,
+    external: Option<bool>,
+    backend: Option<Backend>,
+) -> Result<(PathBuf, Option<PathBuf>), String> {
+    let in_tree = root.join(storage::INDEX_FILE);
+    let data_dir = registry::data_dir();
+    let registered = match &data_dir {
+        Some(dir) => Registry::load(dir)?
+            .index_for(root)
+            .map(path::Path::to_path_buf),
+        None => None,
+    };
+
+    match (external, registered, data_dir) {
+        (Some(false), Some(index), Some(dir)) => {
+            Registry::update(&dir, |registry| registry.unregister(root))?;
+            Ok((in_tree, Some(index)))
+        }
+        (Some(false), _, _) | (None, None, _) => Ok((in_tree, None)),
+        (_, Some(index), _) => Ok((index, None)),
+        (Some(true), None, Some(dir)) => {
+            let backend = backend.unwrap_or_else(|| Backend::detect(&in_tree));
+            let index = Registry::update(&dir, |registry| {
+                registry.register(root, &dir, backend.extension())
+            })?;
+            Ok((index, in_tree.is_file().then_some(in_tree)))
+        }
+        (Some(true), None, None) => Err(String::from(
+            "Cannot find a data directory.  Set XDG_DATA_HOME or HOME.",
+        )),
+    }
+}
+
+/// Plan fixes for lint findings, and make them.
+///
+/// With `dry_run` the fixes are only shown, and with `confirm`
+/// each fix is asked about first.  Every move goes through
+/// [`System::move_path`], so the index is kept up to date.
+fn fix_lints(
+    lock: &IndexLock,
+    mut system: System,
+    layout: Layout,
+    dry_run: bool,
+    confirm: bool,
+) -> Result<(), ()> {
+    let root = system.path.clone();
+    let fixes = lint::plan_fixes(&root, layout, &system);
+
+    if fixes.is_empty() {
+        println!("{} Nothing to fix.", "Ok:".green());
+        return Ok(());
+    }
+
+    for fix in fixes {
+        println!(
+            "{} {} -> {} ({})",
+            "Fix:".cyan(),
+            fix.from.display(),
+            fix.to.display(),
+            fix.reasons.join(", ")
+        );
+
+        if dry_run || (confirm && !ask("Make this fix?")) {
+            continue;
+        }
+
+        match system.move_path(&fix.from, &fix.to) {
+            Ok(_) => println!("{} {}", "Moved".green(), fix.to.display()),
+            Err(message) => println!("{} {}", "Error:".magenta(), message),
+        }
+    }
+
+    if !dry_run {
+        print_error(lock.write(&system))?;
+    }
+    println!();
+
+    Ok(())
+}
+
+/// Print the stats of a system for people to read.
+fn print_stats(stats: &Stats, days: u64) {
+    for area in &stats.areas {
+        let project = area
+            .project
+            .map(|p| format!("{:0>3} ", p))
+            .unwrap_or_default();
+        println!(
+            "{}{}{}  {}/10 categories, {} ids",
+            project, area.area, area.label, area.categories_used, area.ids
+        );
+        for category in &area.categories {
+            println!(
+                "  {}{}  {}/{} used, {} free ({}%)",
+                category.category,
+                category.label,
+                category.used,
+                category.capacity,
+                category.free,
+                category.percent_full
+            );
+        }
+    }
+
+    if !stats.gaps.is_empty() {
+        println!("\nLargest gaps:");
+        for gap in &stats.gaps {
+            if gap.size == 1 {
+                println!("  {}  1 free", gap.first);
+            } else {
+                println!("  {} to {}  {} free", gap.first, gap.last, gap.size);
+            }
+        }
+    }
+    if !stats.growing.is_empty() {
+        println!("\nFastest growing in the last {} days:", days);
+        for growth in &stats.growing {
+            println!(
+                "  {}{}  {} added",
+                growth.category, growth.label, growth.added
+            );
+        }
+    }
+    println!("\nArchived: {} ids", stats.archived);
 
-    let text = match shell {
-        InitShell::Fish => "
-function j
-    pushd $(jd path $argv)
-end"
-        .to_string(),
-        InitShell::Bash => r#"
-function j(){
-    cd $(jd path "$@")
+    for warning in &stats.warnings {
+        println!("{} {}", "Warning:".yellow(), warning);
+    }
 }
-"#
-        .to_string(),
-        InitShell::Zsh => r#"
-function j(){
-    cd $(jd path "$@")
+
+/// Print the children of a usage report, and what is in them.
+fn print_usage(node: &system::Node<usage::Total>, depth: usize) {
+    for child in &node.children {
+        println!(
+            "{:>10} {:>8} files  {}{}",
+            usage::human_size(child.value.bytes),
+            child.value.files,
+            "  ".repeat(depth),
+            child.name
+        );
+        print_usage(child, depth + 1);
+    }
 }
 
-"#
-        .to_string(),
-        _ => {
-            format!("{} {}","Error:".magenta(),"Unsupported shell.  The list of supported shells is currently bash, zsh, and fish.  More will be added eventually.")
-            // TODO Change error message when adding shells
+/// Print the area, category and ID a path is in.
+fn which(path: &path::Path) -> Result<(), String> {
+    let path = path::absolute(path).map_err(|_| format!("Cannot find {}.", path.display()))?;
+    // The system's root is canonical, so the path has to be too.
+    let path = path.canonicalize().unwrap_or(path);
+
+    let index = storage::find_index_for(&path).ok_or("Not in a valid Johnny Decimal system")?;
+    let system = storage::read_index(&index)?;
+    let place = system
+        .which(&path)
+        .ok_or("Not in a valid Johnny Decimal system")?;
+
+    println!("{:<10}{}", "Root:", system.path.display());
+    if let Some(project) = &place.project {
+        println!("{:<10}{}", "Project:", project);
+    }
+    if let Some(area) = &place.area {
+        println!("{:<10}{}", "Area:", area);
+    }
+    if let Some(category) = &place.category {
+        println!("{:<10}{}", "Category:", category);
+    }
+    if let Some(jd) = &place.id {
+        if system.id.contains(jd) {
+            println!("{:<10}{}", "ID:", jd);
+        } else {
+            println!("{:<10}{} {}", "ID:", jd, "(not in the index)".yellow());
         }
+    }
+    Ok(())
+}
+
+/// Merge two copies of an index, and write the merged index.
+fn merge_indexes(
+    a: PathBuf,
+    b: PathBuf,
+    base: Option<PathBuf>,
+    renumber: Option<Side>,
+    output: Option<PathBuf>,
+) -> Result<(), ()> {
+    let output = output.unwrap_or_else(|| a.clone());
+    let lock = print_error(IndexLock::acquire(&output))?;
+
+    // Reading an index may upgrade it, which needs the lock if it is the output.
+    let a_system = print_error(lock.read_index(&a))?;
+    let b_system = print_error(lock.read_index(&b))?;
+    let base = match base {
+        Some(base) => Some(print_error(lock.read_index(&base))?),
+        None => None,
+    };
+    let base = match &base {
+        Some(base) => Base::Index(base),
+        None => Base::Disk,
     };
 
+    let merged = merge::merge(base, a_system, b_system);
+
+    let mut resolutions = Vec::new();
+    for collision in &merged.collisions {
+        println!(
+            "{} {} (a) and {} (b)",
+            "Collision:".red(),
+            collision.a,
+            collision.b
+        );
+        let resolution = match renumber {
+            Some(side) => Resolution::Renumber(side),
+            None => print_error(choose_resolution())?,
+        };
+        resolutions.push(resolution);
+    }
+
+    let (system, renumbered) = print_error(merged.resolve(&resolutions))?;
+
+    // Move the folders of renumbered numbers to their new names.
+    for number in &renumbered {
+        let from = system.get_path(&number.from);
+        let to = system.get_path(&number.to);
+        if from.exists() && !to.exists() {
+            print_error(fs::rename(&from, &to).map_err(|_| "Cannot move folder."))?;
+        }
+        println!("{} {} -> {}", "Renumbered".green(), number.from, number.to);
+    }
+
+    print_error(lock.write_as(&system, Backend::detect(&a)))?;
+    println!("Merged index has been written to {}", output.display());
+
+    Ok(())
+}
+
+/// Ask how to settle a collision between two numbers.
+///
+/// Settling a collision can move folders, so when there is nobody to ask
+/// this fails instead of guessing.
+fn choose_resolution() -> Result<Resolution, &'static str> {
+    const NOT_INTERACTIVE: &str = "Collisions need --renumber when not interactive.";
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        return Err(NOT_INTERACTIVE);
+    }
+    loop {
+        print!("Keep [a], keep [b], renumber a [ra] or renumber b [rb]? ");
+        let _ = io::stdout().flush();
+
+        let mut answer = String::new();
+        if io::stdin().read_line(&mut answer).is_err() || answer.is_empty() {
+            return Err(NOT_INTERACTIVE);
+        }
+        match answer.trim().to_lowercase().as_str() {
+            "a" => return Ok(Resolution::Keep(Side::A)),
+            "b" => return Ok(Resolution::Keep(Side::B)),
+            "ra" => return Ok(Resolution::Renumber(Side::A)),
+            "rb" => return Ok(Resolution::Renumber(Side::B)),
+            _ => continue,
+        }
+    }
+}
+
+/// Ask a yes or no question on the terminal.
+fn ask(question: &str) -> bool {
+    print!("{} [y/N] ", question);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
+fn init(shell: InitShell) {
+    // use the libc c interface to check if stdout is a tty or a pipe.
+    let istty = unsafe { libc::isatty(libc::STDOUT_FILENO as i32) } != 0;
+
+    let text = shell::init_script(shell);
+
     // if it is a tty, print a warning message
     if istty {
         println!("{} This command is not meant to be used in the terminal.  Use it in your shell config to set up the ability to cd to Johnny Decimal numbers.
@@ -223,26 +942,101 @@ Here is what would normally be output:
     }
 }
 
-fn go_to_jd(input: String) -> Result<(), String> {
-    let system = get_system()?;
+fn go_to_jd(input: String, dir: bool) -> Result<(), String> {
+    let path = match JdNumber::try_from(input.clone()) {
+        Ok(jd_term) => {
+            let system = get_system_for(&jd_term)?;
+            let jd = system.get_id(jd_term)?;
+            id_path(&system, &jd, dir)?
+        }
+        // Not a number, so it is a category, an area or part of a label.
+        Err(_) => {
+            let system = get_system()?;
+            match jump::resolve(&system, &input)? {
+                Target::Folder(path) => path,
+                Target::Id(jd) => id_path(&system, jd, dir)?,
+                Target::Choices(choices) => id_path(&system, choose_number(&choices)?, dir)?,
+            }
+        }
+    };
+
+    print_path(&path);
+
+    Ok(())
+}
+
+/// Get the path to print for a JD number.
+fn id_path(system: &System, jd: &JdNumber, dir: bool) -> Result<PathBuf, String> {
+    let mut path = system.get_primary_path(jd)?;
+
+    // You cannot cd into a file, so go to the folder it is in.
+    if dir && jd.primary_location().is_some_and(|l| l.is_file()) {
+        path.pop();
+    }
+    Ok(path)
+}
+
+/// Ask which of some JD numbers was meant.
+///
+/// Everything is written to stderr, as stdout is the path that `j` changes to.
+fn choose_number<'a>(choices: &[&'a JdNumber]) -> Result<&'a JdNumber, String> {
+    eprintln!("More than one JD number matches:");
+    for (i, jd) in choices.iter().enumerate() {
+        eprintln!("{:>3}) {}", i + 1, jd);
+    }
+    if unsafe { libc::isatty(libc::STDIN_FILENO) } == 0 {
+        return Err(String::from("Give more of the label to choose one."));
+    }
+
+    eprint!("Which one? ");
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return Err(String::from("No JD number was chosen."));
+    }
+    match answer.trim().parse::<usize>() {
+        Ok(choice) if (1..=choices.len()).contains(&choice) => Ok(choices[choice - 1]),
+        _ => Err(String::from("No JD number was chosen.")),
+    }
+}
+
+/// Print a path on its own line.
+///
+/// On unix the raw bytes are printed, so that paths which are
+/// not valid UTF-8 can still be used by the shell.
+fn print_path(path: &path::Path) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::OsStrExt;
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(path.as_os_str().as_bytes());
+        let _ = stdout.write_all(b"\n");
+    }
+    #[cfg(not(unix))]
+    println!("{}", path.display());
+}
+
+/// Open a Johnny Decimal number, file or folder, with the default application.
+fn open_jd(input: String) -> Result<(), String> {
     let jd_term = JdNumber::try_from(input)?;
+    let system = get_system_for(&jd_term)?;
     let jd = system.get_id(jd_term)?;
 
-    // let path = format!(
-    //     "{}/{}",
-    //     system.path.to_str().unwrap(),
-    //     jd.get_relative_path()
-    // );
-    let mut path = system.path;
-    path.push(jd.get_relative_path());
+    let path = system.get_primary_path(&jd)?;
 
-    println!("{}", path.display());
+    let mut command = if cfg!(target_os = "windows") {
+        let mut command = Command::new("cmd");
+        command.args(["/C", "start", ""]);
+        command
+    } else if cfg!(target_os = "macos") {
+        Command::new("open")
+    } else {
+        Command::new("xdg-open")
+    };
 
-    return Ok(());
-    // match env::set_current_dir(path) {
-    //     Ok(_) => return Ok(()),
-    //     Err(_) => return Err("Unable to change to the correct directory."),
-    // };
+    match command.arg(&path).status() {
+        Ok(status) if status.success() => Ok(()),
+        _ => Err(format!("Could not open {}", path.display())),
+    }
 }
 
 // fn display_overview() -> Result<String, &'static str> {
@@ -261,70 +1055,39 @@ fn go_to_jd(input: String) -> Result<(), String> {
 //     return Ok(());
 // }
 
-/// Find the jd index file
-// taken from https://codereview.stackexchange.com/questions/236743/find-a-file-in-current-or-parent-directories
-fn find_index() -> Option<String> {
-    let mut path = env::current_dir().unwrap();
-    let file = path::Path::new(".JdIndex");
-
-    loop {
-        path.push(file);
-
-        if path.is_file() {
-            break Some(fs::read_to_string(path).unwrap());
-        }
-
-        if !(path.pop() && path.pop()) {
-            break None;
-        }
-    }
+/// Read the system the current directory is in.
+fn get_system() -> Result<System, String> {
+    let index = storage::find_index().ok_or("Not in a valid Johnny Decimal system")?;
+    storage::read_index(&index)
 }
 
-fn write_index(system: System) -> Result<(), &'static str> {
-    let mut path = env::current_dir().unwrap();
-    let file = path::Path::new(".JdIndex");
-
-    loop {
-        path.push(file);
-
-        if path.is_file() {
-            break;
-        }
-
-        if !(path.pop() && path.pop()) {
-            return Err("Could not find index file to write to.");
-        }
-    }
-
-    match fs::write(
-        path,
-        ron::ser::to_string_pretty(&system, ron::ser::PrettyConfig::new()).unwrap(),
-    ) {
-        Ok(_result) => Ok(()),
-        Err(_err) => Err("Cannot write to file."),
-    }
+/// Read the system the current directory is in, with only the number `jd` in it.
+///
+/// This is faster than reading the whole system for backends that can
+/// look up one number.
+fn get_system_for(jd: &JdNumber) -> Result<System, String> {
+    let index = storage::find_index().ok_or("Not in a valid Johnny Decimal system")?;
+    storage::read_id(&index, jd)
 }
 
-fn get_system() -> Result<System, &'static str> {
-    let index = match find_index() {
-        Some(index) => index,
-        None => return Err("Not in a valid Johnny Decimal system"),
-    };
-
-    let system: System = match ron::from_str(&index) {
-        Ok(x) => x,
-        Err(_) => return Err("Cannot read index file."),
-    };
-    return Ok(system);
+/// Lock the index of the system the current directory is in, and read it.
+///
+/// Changes to the system should be written back with the returned lock,
+/// so that nothing else changes the index in between.
+fn lock_system() -> Result<(IndexLock, System), String> {
+    let index = storage::find_index().ok_or("Not in a valid Johnny Decimal system")?;
+    let lock = IndexLock::acquire(&index)?;
+    let system = lock.read()?;
+    Ok((lock, system))
 }
 
 /// Search for a johnny decimal number.
-fn _search(search: &str) -> Result<JdNumber, &str> {
+fn _search(search: &str) -> Result<JdNumber, String> {
     let re = Regex::new(r"(\d{3})?\.?(\d{2})\.(\d{2})").unwrap();
 
     let captures = match re.captures(search) {
         Some(x) => x,
-        None => return Err("kj"),
+        None => return Err(String::from("kj")),
     };
 
     let category: u32 = captures.get(2).unwrap().as_str().parse().unwrap();
@@ -348,10 +1111,10 @@ fn _search(search: &str) -> Result<JdNumber, &str> {
 
     let system = get_system()?;
 
-    return match system.id.binary_search(&to_find) {
-        Ok(index) => Ok(system.id[index].clone()),
-        Err(_) => Err("Cannot find number"),
-    };
+    match system.id.get(&to_find) {
+        Some(jd) => Ok(jd.clone()),
+        None => Err(String::from("Cannot find number")),
+    }
 
     // // Regular linear search.  Sometime I might want to change this to a binary search.
     // for jd in system.id {
@@ -361,14 +1124,3 @@ fn _search(search: &str) -> Result<JdNumber, &str> {
     // }
     // return Err("Cannot find number");
 }
-
-/// Checks if a given file or directory is hidden.
-///
-/// Taken from https://docs.rs/walkdir/latest/walkdir/
-fn is_hidden(entry: &DirEntry) -> bool {
-    entry
-        .file_name()
-        .to_str()
-        .map(|s| s.starts_with("."))
-        .unwrap_or(false)
-}
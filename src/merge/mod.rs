@@ -0,0 +1,366 @@
+use crate::jdnumber::{JdNumber, Location};
+use crate::system::System;
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+/// One of the two indexes being merged.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Side {
+    A,
+    B,
+}
+
+impl FromStr for Side {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "a" => Ok(Side::A),
+            "b" => Ok(Side::B),
+            _ => Err(String::from("unknown side.  Use a or b.")),
+        }
+    }
+}
+
+/// What two indexes are merged against.
+#[derive(Debug, Clone, Copy)]
+pub enum Base<'a> {
+    /// An older index that both were changed from.
+    Index(&'a System),
+    /// The system as it is on disk.
+    Disk,
+}
+
+/// The same number, changed differently in both indexes.
+#[derive(Debug, Clone)]
+pub struct Collision {
+    pub a: JdNumber,
+    pub b: JdNumber,
+}
+
+/// How to settle a collision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resolution {
+    /// Keep the number from one side, and drop the other.
+    Keep(Side),
+    /// Keep both, giving the number from this side the next free id.
+    Renumber(Side),
+}
+
+/// Two indexes merged together, with the collisions still to settle.
+#[derive(Debug)]
+pub struct Merge {
+    /// Everything that merged cleanly.
+    pub system: System,
+    pub collisions: Vec<Collision>,
+}
+
+/// A number that was given a new id to settle a collision.
+#[derive(Debug, Clone)]
+pub struct Renumbered {
+    pub from: JdNumber,
+    pub to: JdNumber,
+}
+
+/// Check if two copies of a number are the same.
+///
+/// Folder and file paths are left out, because they follow from the
+/// labels and differ between indexes made from different working directories.
+fn same(a: &JdNumber, b: &JdNumber) -> bool {
+    let on_disk = |l: &Location| matches!(l, Location::Path(_) | Location::File(_));
+
+    a.project_label == b.project_label
+        && a.project_area_label == b.project_area_label
+        && a.label == b.label
+        && a.area_label == b.area_label
+        && a.category_label == b.category_label
+        && a.locations == b.locations
+        && ((on_disk(&a.path) && on_disk(&b.path)) || a.path == b.path)
+}
+
+fn by_number(system: System) -> BTreeMap<(Option<u32>, u32, u32), JdNumber> {
+    system
+        .id
+        .into_iter()
+        .map(|jd| ((jd.project, jd.category, jd.id), jd))
+        .collect()
+}
+
+/// Merge two indexes of the same system.
+///
+/// Against an older index, numbers added or changed on one side are
+/// taken from that side, and numbers removed on one side and unchanged
+/// on the other are removed.  Numbers added or changed differently on
+/// both sides are collisions.
+///
+/// Against the disk, numbers on one side only are kept, and when the
+/// sides differ the one whose folder or file exists is taken.  If both
+/// or neither exist, it is a collision.
+///
+/// The root, layout and labels are taken from `a`, with labels only `b`
+/// has added to them.
+pub fn merge(base: Base, a: System, b: System) -> Merge {
+    let mut system = System::new(a.path.clone());
+    system.layout = a.layout;
+    system.area_labels = b.area_labels.clone();
+    system.area_labels.extend(a.area_labels.clone());
+    system.category_labels = b.category_labels.clone();
+    system.category_labels.extend(a.category_labels.clone());
+    // Cached usage is checked before it is used, so either side's will do.
+    system.usage = b.usage.clone();
+    system.usage.extend(a.usage.clone());
+
+    let old: BTreeMap<_, _> = match base {
+        Base::Index(base) => base
+            .id
+            .iter()
+            .map(|jd| ((jd.project, jd.category, jd.id), jd))
+            .collect(),
+        Base::Disk => BTreeMap::new(),
+    };
+    let mut a = by_number(a);
+    let mut b = by_number(b);
+
+    let mut numbers: Vec<_> = a.keys().chain(b.keys()).copied().collect();
+    numbers.sort();
+    numbers.dedup();
+
+    let mut collisions = Vec::new();
+    for number in numbers {
+        let old = old.get(&number).copied();
+        let kept = match (a.remove(&number), b.remove(&number)) {
+            (Some(a), Some(b)) if same(&a, &b) => Some(a),
+            (Some(a), Some(b)) => {
+                let winner = match base {
+                    Base::Index(_) => match old {
+                        Some(old) if same(old, &a) => Some(Side::B),
+                        Some(old) if same(old, &b) => Some(Side::A),
+                        _ => None,
+                    },
+                    Base::Disk => {
+                        match (system.get_path(&a).exists(), system.get_path(&b).exists()) {
+                            (true, false) => Some(Side::A),
+                            (false, true) => Some(Side::B),
+                            _ => None,
+                        }
+                    }
+                };
+                match winner {
+                    Some(Side::A) => Some(a),
+                    Some(Side::B) => Some(b),
+                    None => {
+                        collisions.push(Collision { a, b });
+                        None
+                    }
+                }
+            }
+            // Removed on one side; keep it only if the other side changed it.
+            (Some(jd), None) | (None, Some(jd)) => match old {
+                Some(old) if same(old, &jd) => None,
+                _ => Some(jd),
+            },
+            (None, None) => None,
+        };
+
+        if let Some(jd) = kept {
+            // Numbers are unique here, so this cannot fail.
+            let _ = system.add_id(jd);
+        }
+    }
+
+    Merge { system, collisions }
+}
+
+impl Merge {
+    /// Settle the collisions, one resolution for each, and get the merged system.
+    ///
+    /// The numbers that were given a new id are returned too, so their
+    /// folders can be moved.
+    pub fn resolve(
+        self,
+        resolutions: &[Resolution],
+    ) -> Result<(System, Vec<Renumbered>), &'static str> {
+        if resolutions.len() != self.collisions.len() {
+            return Err("Every collision needs a resolution.");
+        }
+        let mut system = self.system;
+
+        // Add every number that keeps its id first, so that renumbered
+        // numbers cannot take an id that one of them needs.
+        let mut to_renumber = Vec::new();
+        for (collision, resolution) in self.collisions.into_iter().zip(resolutions) {
+            let (kept, other) = match resolution {
+                Resolution::Keep(Side::A) | Resolution::Renumber(Side::B) => {
+                    (collision.a, collision.b)
+                }
+                Resolution::Keep(Side::B) | Resolution::Renumber(Side::A) => {
+                    (collision.b, collision.a)
+                }
+            };
+            let _ = system.add_id(kept);
+            if let Resolution::Renumber(_) = resolution {
+                to_renumber.push(other);
+            }
+        }
+
+        let mut renumbered = Vec::new();
+        for from in to_renumber {
+            let next = system
+                .id
+                .in_category(from.project, from.category)
+                .next_back()
+                .map(|jd| jd.id)
+                .unwrap_or(0)
+                + 1;
+            if next > from.max_id() {
+                return Err("Category is full, so a number cannot be renumbered.");
+            }
+
+            let mut to = from.clone();
+            to.id = next;
+            // Folders and files are named after the number, so they move with it.
+            to.path = match &from.path {
+                Location::Path(_) => Location::Path(system.get_path(&to)),
+                Location::File(_) => Location::File(system.get_path(&to)),
+                other => other.clone(),
+            };
+
+            let _ = system.add_id(to.clone());
+            renumbered.push(Renumbered { from, to });
+        }
+
+        Ok((system, renumbered))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{merge, Base, Resolution, Side};
+    use crate::jdnumber::{JdNumber, Location};
+    use crate::system::System;
+    use crate::testing::TempRoot;
+    use std::path::PathBuf;
+
+    fn system(ids: &[&str]) -> System {
+        let mut system = System::new(PathBuf::from("/jd"));
+        for id in ids {
+            let mut jd = JdNumber::try_from(PathBuf::from(id)).unwrap();
+            jd.path = Location::Path(system.get_path(&jd));
+            system.fill_labels(&mut jd);
+            system.add_id(jd).unwrap();
+        }
+        system
+    }
+
+    fn numbers(system: &System) -> Vec<String> {
+        system.id.iter().map(|jd| jd.to_string()).collect()
+    }
+
+    #[test]
+    fn test_merge_clean() {
+        let base = system(&[
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+            "10-19_finance/12_payroll/12.02_oct_payroll",
+        ]);
+        let a = system(&[
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+            "10-19_finance/12_payroll/12.02_oct_payroll",
+            "10-19_finance/12_payroll/12.03_nov_payroll",
+        ]);
+        let b = system(&[
+            "10-19_finance/12_payroll/12.01_september_payroll",
+            "20-29_admin/22_contracts/22.01_cleaning_contract",
+        ]);
+
+        let merged = merge(Base::Index(&base), a, b);
+        assert!(merged.collisions.is_empty());
+        assert_eq!(
+            numbers(&merged.system),
+            [
+                // renamed on b
+                "12.01_september_payroll",
+                // 12.02 was removed on b
+                // added on a
+                "12.03_nov_payroll",
+                // added on b
+                "22.01_cleaning_contract",
+            ]
+        );
+        assert_eq!(
+            merged.system.category_labels.get(&22).unwrap(),
+            "_contracts"
+        );
+    }
+
+    #[test]
+    fn test_merge_collisions() {
+        let base = system(&["10-19_finance/12_payroll/12.01_sept_payroll"]);
+        let a = system(&[
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+            "10-19_finance/12_payroll/12.02_oct_payroll",
+        ]);
+        let b = system(&[
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+            "10-19_finance/12_payroll/12.02_bonus",
+        ]);
+
+        let merged = merge(Base::Index(&base), a, b);
+        assert_eq!(merged.collisions.len(), 1);
+        assert_eq!(merged.collisions[0].a.label, "_oct_payroll");
+        assert_eq!(merged.collisions[0].b.label, "_bonus");
+
+        let (resolved, renumbered) = merged.resolve(&[Resolution::Renumber(Side::B)]).unwrap();
+        assert_eq!(
+            numbers(&resolved),
+            ["12.01_sept_payroll", "12.02_oct_payroll", "12.03_bonus"]
+        );
+        assert_eq!(renumbered.len(), 1);
+        assert_eq!(
+            renumbered[0].to.path,
+            Location::Path(PathBuf::from("/jd/10-19_finance/12_payroll/12.03_bonus"))
+        );
+    }
+
+    #[test]
+    fn test_merge_against_disk() {
+        let root = TempRoot::new("merge_disk");
+        std::fs::create_dir_all(root.join("10-19_finance/12_payroll/12.02_bonus")).unwrap();
+
+        let on_disk = |ids: &[&str]| {
+            let mut system = system(ids);
+            system.path = root.to_path_buf();
+            system
+        };
+        let a = on_disk(&[
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+            "10-19_finance/12_payroll/12.02_oct_payroll",
+        ]);
+        let b = on_disk(&["10-19_finance/12_payroll/12.02_bonus"]);
+
+        let merged = merge(Base::Disk, a, b);
+        assert!(merged.collisions.is_empty());
+        // 12.01 is only on a, and 12.02_bonus has a folder
+        assert_eq!(
+            numbers(&merged.system),
+            ["12.01_sept_payroll", "12.02_bonus"]
+        );
+    }
+
+    #[test]
+    fn test_keep_side() {
+        let base = system(&[]);
+        let a = system(&["10-19_finance/12_payroll/12.01_sept_payroll"]);
+        let b = system(&["10-19_finance/12_payroll/12.01_bonus"]);
+
+        let merged = merge(Base::Index(&base), a, b);
+        assert!(merged.resolve(&[]).is_err());
+
+        let a = system(&["10-19_finance/12_payroll/12.01_sept_payroll"]);
+        let b = system(&["10-19_finance/12_payroll/12.01_bonus"]);
+        let (resolved, renumbered) = merge(Base::Index(&base), a, b)
+            .resolve(&[Resolution::Keep(Side::B)])
+            .unwrap();
+        assert_eq!(numbers(&resolved), ["12.01_bonus"]);
+        assert!(renumbered.is_empty());
+    }
+}
@@ -0,0 +1,98 @@
+use crate::jdnumber::{JdNumber, Layout};
+use std::path::{Path, PathBuf};
+
+/// The format of a prompt if none is given.
+pub const DEFAULT_FORMAT: &str = "[{number} {label}]";
+
+/// Find the id that `path` is in, from the names of its folders alone.
+///
+/// `root` is the root folder of the system.  Nothing is read from disk,
+/// not even the index, so the layout of the system is not known; the
+/// path is tried against each layout in turn.
+pub fn find(root: &Path, path: &Path) -> Option<JdNumber> {
+    let relative = path.strip_prefix(root).ok()?;
+
+    let mut so_far = PathBuf::new();
+    for component in relative.components() {
+        so_far.push(component);
+        for layout in [Layout::Full, Layout::NoArea, Layout::CategoryOnly] {
+            // What is inside an id belongs to it, so the first id found is the one.
+            if let Ok(jd) = JdNumber::from_path(so_far.clone(), layout) {
+                return Some(jd);
+            }
+        }
+    }
+    None
+}
+
+/// Make a label readable, like `oct payroll` for `_oct_payroll`.
+pub fn readable(label: &str) -> String {
+    label.trim_start_matches([' ', '_', '-']).replace('_', " ")
+}
+
+/// Fill in a prompt format for a JD number.
+///
+/// The format can have these placeholders:
+/// - `{number}`: `12.02`
+/// - `{label}`: `oct payroll`
+/// - `{category}`: `12 payroll`
+/// - `{area}`: `10-19 finance`
+pub fn render(format: &str, jd: &JdNumber) -> String {
+    format
+        .replace("{number}", &jd.number())
+        .replace("{label}", &readable(&jd.label))
+        .replace(
+            "{category}",
+            format!("{:0>2} {}", jd.category, readable(&jd.category_label)).trim_end(),
+        )
+        .replace(
+            "{area}",
+            format!("{} {}", jd.get_area_range(), readable(&jd.area_label)).trim_end(),
+        )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find, render, DEFAULT_FORMAT};
+    use std::path::Path;
+
+    #[test]
+    fn test_find() {
+        let root = Path::new("/home/calvin/jd");
+        let jd = find(
+            root,
+            &root.join("10-19_finance/12_payroll/12.02_oct_payroll/scans"),
+        )
+        .unwrap();
+        assert_eq!(jd.number(), "12.02");
+
+        // other layouts
+        let jd = find(root, &root.join("12_payroll/12.02_oct_payroll")).unwrap();
+        assert_eq!(jd.number(), "12.02");
+        let jd = find(root, &root.join("10-19_finance/12.02_oct_payroll")).unwrap();
+        assert_eq!(jd.number(), "12.02");
+
+        assert!(find(root, &root.join("10-19_finance/12_payroll")).is_none());
+        assert!(find(root, Path::new("/home/calvin/12.02_elsewhere")).is_none());
+    }
+
+    #[test]
+    fn test_render() {
+        let root = Path::new("/jd");
+        let jd = find(
+            root,
+            &root.join("10-19_finance/12_payroll/12.02_oct_payroll"),
+        )
+        .unwrap();
+
+        assert_eq!(render(DEFAULT_FORMAT, &jd), "[12.02 oct payroll]");
+        assert_eq!(
+            render("{area} > {category} > {number}", &jd),
+            "10-19 finance > 12 payroll > 12.02"
+        );
+
+        // without category folders the category has no label
+        let jd = find(root, &root.join("10-19_finance/12.02_oct_payroll")).unwrap();
+        assert_eq!(render("{category}", &jd), "12");
+    }
+}
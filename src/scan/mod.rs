@@ -0,0 +1,161 @@
+use crate::jdignore::IgnoreRules;
+use crate::jdnumber::{JdNumber, Layout, Location};
+use rayon::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// How to walk a system when indexing it.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ScanOptions {
+    /// How the folders of the system are laid out.
+    pub layout: Layout,
+    /// Also walk hidden files and folders.
+    pub include_hidden: bool,
+    /// Also look inside id folders.
+    ///
+    /// What is inside an id belongs to that id, so by default the walk
+    /// stops at id folders.
+    pub inside_ids: bool,
+}
+
+/// Everything found by walking a system.
+#[derive(Debug, Default)]
+pub struct Scan {
+    /// The JD numbers found, sorted by number and then by path.
+    pub ids: Vec<JdNumber>,
+    /// Things that could not be walked or indexed, and why.
+    pub skipped: Vec<(PathBuf, String)>,
+}
+
+impl Scan {
+    fn add(&mut self, other: Scan) {
+        self.ids.extend(other.ids);
+        self.skipped.extend(other.skipped);
+    }
+}
+
+/// Find every JD number in the system at `root`.
+///
+/// Folders are walked in parallel, so areas and categories on slow
+/// disks are read at the same time.  Anything matched by a `.jdignore` is
+/// left out.
+pub fn scan(root: &Path, options: ScanOptions) -> Scan {
+    let rules = IgnoreRules::new(root, options.include_hidden);
+
+    let mut scan = scan_folder(root, &rules, options);
+    scan.ids
+        .sort_by(|a, b| a.cmp(b).then_with(|| a.path.path().cmp(&b.path.path())));
+    scan.skipped.sort();
+    scan
+}
+
+/// Walk everything inside `folder`, walking the folders in it in parallel.
+fn scan_folder(folder: &Path, rules: &IgnoreRules, options: ScanOptions) -> Scan {
+    let mut scan = Scan::default();
+
+    let entries = match fs::read_dir(folder) {
+        Ok(entries) => entries,
+        Err(err) => {
+            scan.skipped.push((folder.to_path_buf(), err.to_string()));
+            return scan;
+        }
+    };
+
+    let mut folders = Vec::new();
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(err) => {
+                scan.skipped.push((folder.to_path_buf(), err.to_string()));
+                continue;
+            }
+        };
+        let path = entry.path();
+        let file_type = match entry.file_type() {
+            Ok(file_type) => file_type,
+            Err(err) => {
+                scan.skipped.push((path, err.to_string()));
+                continue;
+            }
+        };
+        if rules.is_ignored(&path, file_type.is_dir()) {
+            continue;
+        }
+
+        let is_id = match JdNumber::from_path(path.clone(), options.layout) {
+            Ok(mut jd) => {
+                // Some ids are a single file rather than a folder.
+                if file_type.is_file() {
+                    jd.path = Location::File(path.clone());
+                }
+                scan.ids.push(jd);
+                true
+            }
+            Err("Name is not valid UTF-8") => {
+                scan.skipped
+                    .push((path.clone(), String::from("name is not valid UTF-8")));
+                false
+            }
+            Err(_) => false,
+        };
+
+        if file_type.is_dir() && (!is_id || options.inside_ids) {
+            folders.push(path);
+        }
+    }
+
+    let inner: Vec<Scan> = folders
+        .par_iter()
+        .map(|folder| scan_folder(folder, rules, options))
+        .collect();
+    for other in inner {
+        scan.add(other);
+    }
+
+    scan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{scan, ScanOptions};
+    use crate::jdnumber::Location;
+    use crate::testing::TempRoot;
+    use std::fs;
+
+    #[test]
+    fn test_scan() {
+        let root = TempRoot::new("scan");
+        let payroll = root.join("10-19_finance/12_payroll");
+        fs::create_dir_all(payroll.join("12.02_oct_payroll/12.05_inside")).unwrap();
+        fs::create_dir_all(payroll.join("12.01_sept_payroll")).unwrap();
+        fs::create_dir_all(root.join("20-29_admin/22_contracts/22.01_lease")).unwrap();
+        fs::create_dir_all(root.join("node_modules/30-39_x/31_y/31.01_z")).unwrap();
+        fs::write(payroll.join("12.03_invoice.pdf"), "").unwrap();
+        fs::write(root.join(".jdignore"), "node_modules\n").unwrap();
+
+        let found = scan(&root, ScanOptions::default());
+        let numbers: Vec<String> = found.ids.iter().map(|jd| jd.to_string()).collect();
+        assert_eq!(
+            numbers,
+            [
+                "12.01_sept_payroll",
+                "12.02_oct_payroll",
+                "12.03_invoice.pdf",
+                "22.01_lease"
+            ]
+        );
+        assert_eq!(
+            found.ids[2].path,
+            Location::File(payroll.join("12.03_invoice.pdf"))
+        );
+        assert!(found.skipped.is_empty());
+
+        // looking inside ids finds what is filed in them
+        let options = ScanOptions {
+            inside_ids: true,
+            ..ScanOptions::default()
+        };
+        let found = scan(&root, options);
+        assert_eq!(found.ids.len(), 5);
+    }
+}
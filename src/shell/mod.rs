@@ -0,0 +1,360 @@
+use crate::prompt::readable;
+use crate::system::System;
+use std::str::FromStr;
+
+/// A shell that `jd init` can set up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InitShell {
+    Bash,
+    Elvish,
+    Fish,
+    Nushell,
+    Posix,
+    Powershell,
+    Xonsh,
+    Zsh,
+}
+
+impl InitShell {
+    /// Every shell, in the order they are listed in.
+    pub const ALL: [InitShell; 8] = [
+        InitShell::Bash,
+        InitShell::Elvish,
+        InitShell::Fish,
+        InitShell::Nushell,
+        InitShell::Posix,
+        InitShell::Powershell,
+        InitShell::Xonsh,
+        InitShell::Zsh,
+    ];
+}
+
+impl FromStr for InitShell {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "bash" => Ok(InitShell::Bash),
+            "elvish" => Ok(InitShell::Elvish),
+            "fish" => Ok(InitShell::Fish),
+            "nushell" => Ok(InitShell::Nushell),
+            "posix" => Ok(InitShell::Posix),
+            "powershell" => Ok(InitShell::Powershell),
+            "xonsh" => Ok(InitShell::Xonsh),
+            "zsh" => Ok(InitShell::Zsh),
+            _ => Err(String::from(
+                "unknown shell.  Use bash, elvish, fish, nushell, posix, powershell, xonsh or zsh.",
+            )),
+        }
+    }
+}
+
+/// Get the shell code for `jd init`: `j`, and completion of numbers for it.
+pub fn init_script(shell: InitShell) -> String {
+    let mut script = String::from(jump(shell));
+    if let Some(completion) = completion(shell) {
+        script.push_str(completion);
+    }
+    script
+}
+
+/// Get the shell code that defines `j`, which changes to the folder of a
+/// Johnny Decimal number.
+///
+/// `j` runs `jd path --dir` with its arguments, and only changes folder if
+/// that succeeds, so an error is shown instead of being used as a path.
+fn jump(shell: InitShell) -> &'static str {
+    match shell {
+        InitShell::Bash | InitShell::Zsh => {
+            r#"
+function j() {
+    local dir
+    dir="$(jd path --dir "$@")" && cd -- "$dir"
+}
+"#
+        }
+        InitShell::Posix => {
+            r#"
+j() {
+    _jd_dir="$(jd path --dir "$@")" && cd -- "$_jd_dir"
+}
+"#
+        }
+        InitShell::Fish => {
+            r#"
+function j
+    set -l dir (jd path --dir $argv | string collect)
+    and pushd $dir
+end
+"#
+        }
+        InitShell::Elvish => {
+            r#"
+fn j {|@args|
+    cd (jd path --dir $@args)
+}
+"#
+        }
+        InitShell::Nushell => {
+            r#"
+def --env j [...args: string] {
+    cd (^jd path --dir ...$args | str trim --right --char "\n")
+}
+"#
+        }
+        InitShell::Powershell => {
+            r#"
+function j {
+    $dir = jd path --dir @args
+    if ($LASTEXITCODE -eq 0) {
+        Set-Location -LiteralPath $dir
+    }
+}
+"#
+        }
+        InitShell::Xonsh => {
+            r#"
+def _jd_j(args):
+    path = $(jd path --dir @(args)).rstrip('\n')
+    if path:
+        cd @(path)
+
+aliases['j'] = _jd_j
+"#
+        }
+    }
+}
+
+/// Get the shell code that completes the numbers given to `j`, with their
+/// labels, from `jd complete`.
+///
+/// Bash cannot show descriptions, so the labels are only shown while
+/// there is more than one number to choose from.
+fn completion(shell: InitShell) -> Option<&'static str> {
+    match shell {
+        InitShell::Bash => Some(
+            r#"
+_jd_complete_j() {
+    local IFS=$'\n'
+    local candidates
+    candidates=($(jd complete -- "$2" 2>/dev/null))
+    if [ "${#candidates[@]}" -eq 1 ]; then
+        COMPREPLY=("${candidates[0]%%$'\t'*}")
+    else
+        COMPREPLY=("${candidates[@]/$'\t'/ }")
+    fi
+}
+complete -F _jd_complete_j j
+"#,
+        ),
+        InitShell::Zsh => Some(
+            r#"
+_jd_complete_j() {
+    local -a candidates
+    candidates=(${(f)"$(jd complete -- "$PREFIX" 2>/dev/null)"})
+    candidates=("${candidates[@]/$'\t'/:}")
+    _describe 'Johnny Decimal number' candidates
+}
+(( $+functions[compdef] )) && compdef _jd_complete_j j
+"#,
+        ),
+        InitShell::Fish => Some(
+            r#"
+complete --command j --no-files --arguments '(jd complete -- (commandline --current-token) 2>/dev/null)'
+"#,
+        ),
+        _ => None,
+    }
+}
+
+/// Get the JD numbers that start with `partial`, each with its label after
+/// a tab, like `12.01\tsept payroll`.
+pub fn complete(system: &System, partial: &str) -> Vec<String> {
+    system
+        .id
+        .iter()
+        .map(|jd| (jd.number(), jd))
+        .filter(|(number, _)| number.starts_with(partial))
+        .map(|(number, jd)| format!("{}\t{}", number, readable(&jd.label)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{complete, init_script, InitShell};
+    use crate::testing::{system_from_paths, TempRoot};
+    use std::fs;
+    use std::path::Path;
+    use std::process::Command;
+
+    /// Get the command that checks the syntax of a script in `shell`,
+    /// without running it.
+    fn syntax_check(shell: InitShell, script: &Path) -> Command {
+        let path = script.to_str().unwrap();
+        let (program, args): (&str, Vec<String>) = match shell {
+            InitShell::Bash => ("bash", vec![String::from("-n"), path.into()]),
+            InitShell::Zsh => ("zsh", vec![String::from("-n"), path.into()]),
+            InitShell::Posix => ("sh", vec![String::from("-n"), path.into()]),
+            InitShell::Fish => ("fish", vec![String::from("--no-execute"), path.into()]),
+            InitShell::Elvish => ("elvish", vec![String::from("-compileonly"), path.into()]),
+            InitShell::Nushell => (
+                "nu",
+                vec![
+                    String::from("--no-config-file"),
+                    String::from("-c"),
+                    format!("nu-check --debug '{}'", path),
+                ],
+            ),
+            InitShell::Powershell => (
+                "pwsh",
+                vec![
+                    String::from("-NoProfile"),
+                    String::from("-Command"),
+                    format!(
+                        "$errors = $null; \
+                         [System.Management.Automation.Language.Parser]::ParseFile('{}', [ref]$null, [ref]$errors) | Out-Null; \
+                         if ($errors) {{ $errors; exit 1 }}",
+                        path
+                    ),
+                ],
+            ),
+            InitShell::Xonsh => (
+                "xonsh",
+                vec![
+                    String::from("--no-rc"),
+                    String::from("-c"),
+                    format!("compilex(open('{}').read(), mode='exec')", path),
+                ],
+            ),
+        };
+        let mut command = Command::new(program);
+        command.args(args);
+        command
+    }
+
+    #[test]
+    fn test_init_syntax() {
+        let folder = TempRoot::new("shell");
+
+        for shell in InitShell::ALL {
+            let script = folder.join(format!("{:?}", shell).to_lowercase());
+            fs::write(&script, init_script(shell)).unwrap();
+
+            // Shells that are not installed are skipped.
+            let output = match syntax_check(shell, &script).output() {
+                Ok(output) => output,
+                Err(_) => continue,
+            };
+            assert!(
+                output.status.success(),
+                "{:?} rejected its script:\n{}",
+                shell,
+                String::from_utf8_lossy(&output.stderr)
+            );
+        }
+    }
+
+    /// Put a stand in for jd, running `body`, first on the path.
+    ///
+    /// Returns the new path.
+    #[cfg(unix)]
+    fn fake_jd(folder: &Path, body: &str) -> String {
+        use std::os::unix::fs::PermissionsExt;
+
+        let jd = folder.join("jd");
+        fs::write(&jd, format!("#!/bin/sh\n{}\n", body)).unwrap();
+        fs::set_permissions(&jd, fs::Permissions::from_mode(0o755)).unwrap();
+        format!(
+            "{}:{}",
+            folder.display(),
+            std::env::var("PATH").unwrap_or_default()
+        )
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_j_with_spaces() {
+        let folder = TempRoot::new("shell_j");
+        let target = folder.join("12 payroll/12.01 sept payroll");
+        fs::create_dir_all(&target).unwrap();
+        // prints the folder of 12.01, and fails for anything else
+        let path = fake_jd(
+            &folder,
+            &format!(
+                "if [ \"$3\" = \"12.01\" ]; then echo '{}'; else echo 'Error' >&2; exit 1; fi",
+                target.display()
+            ),
+        );
+
+        for (shell, program) in [(InitShell::Bash, "bash"), (InitShell::Posix, "sh")] {
+            let script = format!("{}\nj 12.01 && pwd\nj 99.99 || pwd", init_script(shell));
+            let output = match Command::new(program)
+                .args(["-c", &script])
+                .current_dir(&folder)
+                .env("PATH", &path)
+                .output()
+            {
+                Ok(output) => output,
+                Err(_) => continue,
+            };
+            // a failed lookup stays where it is
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                format!("{0}\n{0}\n", target.display()),
+                "{:?}",
+                shell
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn test_bash_completion() {
+        let folder = TempRoot::new("shell_complete");
+        let path = fake_jd(
+            &folder,
+            r#"if [ "$3" = "12." ]; then printf '12.01\tsept payroll\n12.02\toct payroll\n'; else printf '13.01\tledger\n'; fi"#,
+        );
+
+        let script = format!(
+            "{}\n_jd_complete_j j 12. j; printf '%s|' \"${{COMPREPLY[@]}}\"\n_jd_complete_j j 13 j; printf '%s|' \"${{COMPREPLY[@]}}\"",
+            init_script(InitShell::Bash)
+        );
+        if let Ok(output) = Command::new("bash")
+            .args(["-c", &script])
+            .env("PATH", &path)
+            .output()
+        {
+            // labels are shown to choose between numbers, but only the number is filled in
+            assert_eq!(
+                String::from_utf8_lossy(&output.stdout),
+                "12.01 sept payroll|12.02 oct payroll|13.01|"
+            );
+        }
+    }
+
+    #[test]
+    fn test_complete() {
+        let system = system_from_paths(
+            Path::new("/jd"),
+            &[
+                "10-19_finance/12_payroll/12.01_sept_payroll",
+                "10-19_finance/12_payroll/12.02_oct_payroll",
+                "10-19_finance/13_bookkeeping/13.01_ledger",
+            ],
+        );
+
+        assert_eq!(
+            complete(&system, "12."),
+            vec!["12.01\tsept payroll", "12.02\toct payroll"]
+        );
+        assert_eq!(complete(&system, "").len(), 3);
+        assert!(complete(&system, "14").is_empty());
+    }
+
+    #[test]
+    fn test_shell_from_str() {
+        assert_eq!("xonsh".parse::<InitShell>(), Ok(InitShell::Xonsh));
+        assert!("cmd".parse::<InitShell>().is_err());
+    }
+}
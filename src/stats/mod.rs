@@ -0,0 +1,311 @@
+use crate::jdnumber::{JdNumber, Location};
+use crate::system::System;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::time::SystemTime;
+
+/// How many of the largest gaps and fastest growing categories are reported.
+const TOP: usize = 5;
+
+/// How full a Johnny Decimal system is.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    /// How full, in percent, a category or area has to be to be warned about.
+    pub threshold: u32,
+    pub areas: Vec<AreaStats>,
+    /// The longest runs of free ids between used ones, longest first.
+    pub gaps: Vec<Gap>,
+    /// The categories with the most ids added recently, most first.
+    pub growing: Vec<Growth>,
+    /// How many ids are archived.
+    pub archived: usize,
+    pub warnings: Vec<String>,
+}
+
+/// How full an area is.
+#[derive(Debug, Serialize)]
+pub struct AreaStats {
+    pub project: Option<u32>,
+    /// The range of the area, like `10-19`.
+    pub area: String,
+    pub label: String,
+    /// How many of the area's 10 categories have ids.
+    pub categories_used: usize,
+    pub categories_free: usize,
+    /// How many ids are in the area.
+    pub ids: usize,
+    pub categories: Vec<CategoryStats>,
+}
+
+/// How full a category is.
+#[derive(Debug, Serialize)]
+pub struct CategoryStats {
+    /// The number of the category, like `12` or `101.12`.
+    pub category: String,
+    pub label: String,
+    pub used: usize,
+    pub free: usize,
+    /// How many ids the category can hold: 99, or 999 if it is extended.
+    pub capacity: u32,
+    pub percent_full: u32,
+    pub archived: usize,
+}
+
+/// A run of free ids in a category, like `12.05` to `12.40`.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Gap {
+    pub first: String,
+    pub last: String,
+    pub size: u32,
+}
+
+/// How many ids were added to a category recently.
+#[derive(Debug, PartialEq, Eq, Serialize)]
+pub struct Growth {
+    pub category: String,
+    pub label: String,
+    pub added: usize,
+}
+
+/// The ids of each category, keyed by category, of an area.
+type Area<'a> = BTreeMap<u32, Vec<&'a JdNumber>>;
+
+/// Get the number of a category, like `12` or `101.12`.
+fn category_number(project: Option<u32>, category: u32) -> String {
+    match project {
+        Some(project) => format!("{:0>3}.{:0>2}", project, category),
+        None => format!("{:0>2}", category),
+    }
+}
+
+/// Check if a JD number is archived.
+///
+/// A number is archived if a word of its label, or of its category or
+/// area label, starts with "archive", like `12.05_archived_invoices` or
+/// anything in `19_archive`.
+fn is_archived(jd: &JdNumber) -> bool {
+    [&jd.label, &jd.category_label, &jd.area_label]
+        .iter()
+        .flat_map(|label| label.split(|c: char| !c.is_alphanumeric()))
+        .any(|word| word.to_lowercase().starts_with("archive"))
+}
+
+/// Get when a JD number was added, from when its folder or file was created.
+fn added(system: &System, jd: &JdNumber) -> Option<SystemTime> {
+    match jd.path {
+        Location::Path(_) | Location::File(_) => {
+            let metadata = fs::metadata(system.get_path(jd)).ok()?;
+            metadata.created().or_else(|_| metadata.modified()).ok()
+        }
+        _ => None,
+    }
+}
+
+impl Stats {
+    /// Work out how full `system` is.
+    ///
+    /// Categories and areas that are at least `threshold` percent full are
+    /// warned about, and ids added since `since` count towards growth.
+    pub fn new(system: &System, threshold: u32, since: SystemTime) -> Self {
+        // The ids of each category, grouped by area.
+        let mut areas: BTreeMap<(Option<u32>, u32), Area> = BTreeMap::new();
+        for jd in &system.id {
+            areas
+                .entry((jd.project, jd.category / 10 * 10))
+                .or_default()
+                .entry(jd.category)
+                .or_default()
+                .push(jd);
+        }
+
+        let mut stats = Stats {
+            threshold,
+            areas: Vec::new(),
+            gaps: Vec::new(),
+            growing: Vec::new(),
+            archived: 0,
+            warnings: Vec::new(),
+        };
+        let mut gaps: Vec<Gap> = Vec::new();
+
+        for ((project, _), categories) in areas {
+            // Every area has at least one id, or it would not be here.
+            let first = categories.values().next().and_then(|ids| ids.first());
+            let mut area_stats = AreaStats {
+                project,
+                area: first.map(|jd| jd.get_area_range()).unwrap_or_default(),
+                label: first.map(|jd| jd.area_label.clone()).unwrap_or_default(),
+                categories_used: categories.len(),
+                categories_free: 10 - categories.len(),
+                ids: 0,
+                categories: Vec::new(),
+            };
+
+            for (category, ids) in categories {
+                let number = category_number(project, category);
+                let label = ids[0].category_label.clone();
+                let capacity = ids.iter().map(|jd| jd.max_id()).max().unwrap_or(99);
+                let used = ids
+                    .iter()
+                    .filter(|jd| (1..=capacity).contains(&jd.id))
+                    .count();
+                let archived = ids.iter().filter(|jd| is_archived(jd)).count();
+                let percent_full = used as u32 * 100 / capacity;
+
+                if percent_full >= threshold {
+                    stats.warnings.push(format!(
+                        "{}{} is {}% full, with {} ids left.",
+                        number,
+                        label,
+                        percent_full,
+                        capacity as usize - used
+                    ));
+                }
+
+                let added = ids
+                    .iter()
+                    .filter(|jd| added(system, jd).is_some_and(|time| time >= since))
+                    .count();
+                if added > 0 {
+                    stats.growing.push(Growth {
+                        category: number.clone(),
+                        label: label.clone(),
+                        added,
+                    });
+                }
+
+                // The ids are in order, so the gaps are between neighbours.
+                let width = if capacity > 99 { 3 } else { 2 };
+                let mut previous = 0;
+                for id in ids.iter().map(|jd| jd.id).chain([capacity + 1]) {
+                    if id > previous + 1 {
+                        gaps.push(Gap {
+                            first: format!("{}.{:0>width$}", number, previous + 1, width = width),
+                            last: format!("{}.{:0>width$}", number, id - 1, width = width),
+                            size: id - previous - 1,
+                        });
+                    }
+                    previous = previous.max(id);
+                }
+
+                area_stats.ids += ids.len();
+                stats.archived += archived;
+                area_stats.categories.push(CategoryStats {
+                    category: number,
+                    label,
+                    used,
+                    free: capacity as usize - used,
+                    capacity,
+                    percent_full,
+                    archived,
+                });
+            }
+
+            if area_stats.categories_used as u32 * 10 >= threshold {
+                stats.warnings.push(format!(
+                    "Area {}{} has {} of 10 categories in use.",
+                    area_stats.area, area_stats.label, area_stats.categories_used
+                ));
+            }
+            stats.areas.push(area_stats);
+        }
+
+        // The sorts are stable, so ties stay in number order.
+        gaps.sort_by_key(|gap| std::cmp::Reverse(gap.size));
+        gaps.truncate(TOP);
+        stats.gaps = gaps;
+        stats
+            .growing
+            .sort_by_key(|growth| std::cmp::Reverse(growth.added));
+        stats.growing.truncate(TOP);
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Gap, Growth, Stats};
+    use crate::jdnumber::JdNumber;
+    use crate::system::System;
+    use crate::testing::TempRoot;
+    use std::path::PathBuf;
+    use std::time::{Duration, SystemTime};
+
+    fn sample_system(root: PathBuf) -> System {
+        let mut system = System::new(root);
+        for id in (1..=10).chain(50..=94) {
+            system
+                .add_id(JdNumber::try_from(format!("12.{:0>2}", id)).unwrap())
+                .unwrap();
+        }
+        let mut archived = JdNumber::try_from(String::from("13.01")).unwrap();
+        archived.label = String::from("_Archived_invoices");
+        system.add_id(archived).unwrap();
+        system
+            .add_id(JdNumber::try_from(String::from("101.22.01")).unwrap())
+            .unwrap();
+        system
+    }
+
+    #[test]
+    fn test_stats() {
+        let system = sample_system(PathBuf::from("/jd"));
+        let stats = Stats::new(&system, 50, SystemTime::now());
+
+        assert_eq!(stats.areas.len(), 2);
+        let finance = &stats.areas[0];
+        assert_eq!(finance.area, "10-19");
+        assert_eq!(finance.categories_used, 2);
+        assert_eq!(finance.categories_free, 8);
+        assert_eq!(finance.ids, 56);
+
+        let payroll = &finance.categories[0];
+        assert_eq!(payroll.category, "12");
+        assert_eq!(payroll.used, 55);
+        assert_eq!(payroll.free, 44);
+        assert_eq!(payroll.percent_full, 55);
+        assert_eq!(stats.areas[1].categories[0].category, "101.22");
+
+        assert_eq!(
+            stats.gaps[0],
+            Gap {
+                first: String::from("13.02"),
+                last: String::from("13.99"),
+                size: 98,
+            }
+        );
+        assert_eq!(stats.gaps[1].first, "101.22.02");
+        assert_eq!(stats.gaps[2].first, "12.11");
+        assert_eq!(stats.gaps[2].size, 39);
+        assert_eq!(stats.gaps.len(), 4);
+
+        assert_eq!(stats.archived, 1);
+        assert_eq!(stats.warnings.len(), 1);
+        assert!(stats.warnings[0].starts_with("12 is 55% full"));
+        // nothing is on disk, so nothing was added recently
+        assert!(stats.growing.is_empty());
+    }
+
+    #[test]
+    fn test_growth() {
+        let root = TempRoot::new("stats");
+        let system = sample_system(root.to_path_buf());
+        for jd in system.id.iter().filter(|jd| jd.category == 12).take(3) {
+            std::fs::create_dir_all(system.get_path(jd)).unwrap();
+        }
+
+        let an_hour_ago = SystemTime::now() - Duration::from_secs(3600);
+        let stats = Stats::new(&system, 100, an_hour_ago);
+        assert_eq!(
+            stats.growing,
+            vec![Growth {
+                category: String::from("12"),
+                label: String::new(),
+                added: 3
+            }]
+        );
+        assert!(stats.warnings.is_empty());
+    }
+}
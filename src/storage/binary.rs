@@ -0,0 +1,123 @@
+use super::{migrate, replace_file, Storage};
+use crate::system::System;
+use std::fs;
+use std::path::Path;
+
+/// The first bytes of a binary index.
+pub const MAGIC: &[u8] = b"JDINDEX\0";
+
+/// Stores the index in a compact binary form, which is fast to load.
+///
+/// The file is `MAGIC`, then the version as a little endian `u32`,
+/// then the system encoded with bincode.
+pub struct BinaryStorage;
+
+impl Storage for BinaryStorage {
+    fn read(&self, path: &Path) -> Result<(System, u32), String> {
+        let bytes = fs::read(path).map_err(|_| "Cannot read index file.")?;
+
+        let rest = bytes
+            .strip_prefix(MAGIC)
+            .ok_or("Cannot read index file: not a binary index.")?;
+        if rest.len() < 4 {
+            return Err(String::from("Cannot read index file: it is cut short."));
+        }
+        let (version, rest) = rest.split_at(4);
+        let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+
+        // Binary indexes were first written in version 1.
+        let read_error = |e| format!("Cannot read index file: {}", e);
+        let system = match version {
+            1 => migrate::upgrade_old::<migrate::v1::JdNumber>(
+                bincode::deserialize(rest).map_err(read_error)?,
+            ),
+            2 => migrate::upgrade_old::<migrate::v2::JdNumber>(
+                bincode::deserialize(rest).map_err(read_error)?,
+            ),
+            3 => migrate::upgrade_old::<crate::jdnumber::JdNumber>(
+                bincode::deserialize(rest).map_err(read_error)?,
+            ),
+            migrate::CURRENT_VERSION => bincode::deserialize(rest).map_err(read_error)?,
+            _ => {
+                return Err(format!(
+                    "Binary index has version {}, but this jd can only read versions 1 to {}.",
+                    version,
+                    migrate::CURRENT_VERSION
+                ))
+            }
+        };
+        Ok((system, version))
+    }
+
+    fn write(&self, path: &Path, system: &System) -> Result<(), String> {
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(system.version.to_le_bytes());
+        bincode::serialize_into(&mut bytes, system)
+            .map_err(|e| format!("Cannot write index file: {}", e))?;
+
+        replace_file(path, |temp| super::write_synced(temp, &bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BinaryStorage, MAGIC};
+    use crate::jdnumber::Location;
+    use crate::storage::{migrate, Storage};
+    use crate::testing::TempRoot;
+    use std::collections::BTreeMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_bad_binary_index() {
+        let root = TempRoot::new("binary_bad");
+        let path = root.join("index");
+
+        fs::write(&path, MAGIC).unwrap();
+        assert!(BinaryStorage.read(&path).unwrap_err().contains("cut short"));
+
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(99u32.to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+        assert!(BinaryStorage
+            .read(&path)
+            .unwrap_err()
+            .contains("version 99"));
+    }
+
+    #[test]
+    fn test_upgrade_v1() {
+        let root = TempRoot::new("binary_v1");
+        let path = root.join("index");
+        let number = "/jd/100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home";
+        let old = migrate::v1::System {
+            version: 1,
+            path: PathBuf::from("/jd"),
+            layout: Default::default(),
+            area_labels: BTreeMap::new(),
+            category_labels: BTreeMap::new(),
+            id: vec![migrate::v1::JdNumber {
+                project: Some(101),
+                project_label: Some("_website".to_string()),
+                category: 12,
+                id: 1,
+                label: "_home".to_string(),
+                area_label: "_design".to_string(),
+                category_label: "_mockups".to_string(),
+                path: Location::Path(PathBuf::from(number)),
+                locations: Vec::new(),
+            }],
+        };
+        let mut bytes = MAGIC.to_vec();
+        bytes.extend(1u32.to_le_bytes());
+        bincode::serialize_into(&mut bytes, &old).unwrap();
+        fs::write(&path, bytes).unwrap();
+
+        let (system, version) = BinaryStorage.read(&path).unwrap();
+        assert_eq!(version, 1);
+        let jd = system.id.iter().next().unwrap();
+        assert_eq!(jd.project_area_label, Some("_projects".to_string()));
+        assert_eq!(system.get_path(jd), PathBuf::from(number));
+    }
+}
@@ -0,0 +1,367 @@
+use crate::jdnumber::{JdNumber, Layout};
+use crate::system::System;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+/// The version of the index format that is written.
+///
+/// Bump this whenever the serialized form of `System` changes in a way
+/// older indexes cannot be read as, keep the old form in a module below,
+/// and add a step to `upgrade` that turns the old form into the new one.
+pub const CURRENT_VERSION: u32 = 4;
+
+/// Just the version of an index.
+#[derive(Deserialize)]
+struct Probe {
+    /// Indexes from before there was a version have none.
+    #[serde(default)]
+    version: u32,
+}
+
+/// Indexes from before the format had a version.
+mod v0 {
+    use super::*;
+
+    /// A system as it was written before the version field.
+    ///
+    /// Some of these indexes have a `projects` field that was never used;
+    /// it is ignored.
+    #[derive(Deserialize)]
+    pub struct System {
+        #[serde(with = "crate::jdnumber::lossless_path")]
+        pub path: PathBuf,
+        #[serde(default)]
+        pub layout: Layout,
+        #[serde(default)]
+        pub area_labels: BTreeMap<u32, String>,
+        #[serde(default)]
+        pub category_labels: BTreeMap<u32, String>,
+        pub id: Vec<JdNumber>,
+    }
+}
+
+/// A system as it was written in versions 1 to 3, which only differ in
+/// how JD numbers were written.
+///
+/// Version 3 wrote JD numbers as they are now.
+#[derive(Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OldSystem<J> {
+    pub version: u32,
+    #[serde(with = "crate::jdnumber::lossless_path")]
+    pub path: PathBuf,
+    #[serde(default)]
+    pub layout: Layout,
+    #[serde(default)]
+    pub area_labels: BTreeMap<u32, String>,
+    #[serde(default)]
+    pub category_labels: BTreeMap<u32, String>,
+    pub id: Vec<J>,
+}
+
+/// Indexes from before JD numbers remembered their project area.
+pub mod v1 {
+    use super::*;
+    use crate::jdnumber::Location;
+
+    /// A JD number as it was written in version 1.
+    #[derive(Serialize, Deserialize)]
+    pub struct JdNumber {
+        pub project: Option<u32>,
+        pub project_label: Option<String>,
+        pub category: u32,
+        pub id: u32,
+        pub label: String,
+        pub area_label: String,
+        pub category_label: String,
+        pub path: Location,
+        #[serde(default)]
+        pub locations: Vec<Location>,
+    }
+
+    pub type System = OldSystem<JdNumber>;
+
+    impl From<JdNumber> for crate::jdnumber::JdNumber {
+        fn from(old: JdNumber) -> Self {
+            crate::jdnumber::JdNumber {
+                project: old.project,
+                project_label: old.project_label,
+                project_area_label: None,
+                category: old.category,
+                id: old.id,
+                extended: false,
+                label: old.label,
+                area_label: old.area_label,
+                category_label: old.category_label,
+                path: old.path,
+                locations: old.locations,
+            }
+        }
+    }
+}
+
+/// Indexes from before ids could have three digits.
+pub mod v2 {
+    use super::*;
+    use crate::jdnumber::Location;
+
+    /// A JD number as it was written in version 2.
+    #[derive(Serialize, Deserialize)]
+    pub struct JdNumber {
+        pub project: Option<u32>,
+        pub project_label: Option<String>,
+        #[serde(default)]
+        pub project_area_label: Option<String>,
+        pub category: u32,
+        pub id: u32,
+        pub label: String,
+        pub area_label: String,
+        pub category_label: String,
+        pub path: Location,
+        #[serde(default)]
+        pub locations: Vec<Location>,
+    }
+
+    pub type System = OldSystem<JdNumber>;
+
+    impl From<JdNumber> for crate::jdnumber::JdNumber {
+        fn from(old: JdNumber) -> Self {
+            crate::jdnumber::JdNumber {
+                project: old.project,
+                project_label: old.project_label,
+                project_area_label: old.project_area_label,
+                category: old.category,
+                id: old.id,
+                extended: false,
+                label: old.label,
+                area_label: old.area_label,
+                category_label: old.category_label,
+                path: old.path,
+                locations: old.locations,
+            }
+        }
+    }
+}
+
+/// Upgrade an index from version 0 to version 1.
+///
+/// Version 1 drops the `projects` field, and remembers the area and
+/// category labels of every number.
+fn upgrade_v0(old: v0::System) -> System {
+    let mut system = System::new(old.path);
+    system.layout = old.layout;
+    system.area_labels = old.area_labels;
+    system.category_labels = old.category_labels;
+
+    for mut jd in old.id {
+        system.fill_labels(&mut jd);
+        system.id.insert(jd);
+    }
+    // and on to the current version
+    find_project_areas(&mut system);
+
+    system
+}
+
+/// Upgrade an index from version 1, 2 or 3 to the current version.
+///
+/// Version 2 remembers the project area of every number in a project.
+/// Version 3 lets ids have three digits, so every older id has two.
+/// Version 4 caches disk usage, which starts out empty.
+pub fn upgrade_old<J: Into<JdNumber>>(old: OldSystem<J>) -> System {
+    let version = old.version;
+    let mut system = System::new(old.path);
+    system.layout = old.layout;
+    system.area_labels = old.area_labels;
+    system.category_labels = old.category_labels;
+    system.id = old.id.into_iter().map(Into::into).collect();
+    if version < 2 {
+        find_project_areas(&mut system);
+    }
+
+    system
+}
+
+/// Fill in the project area labels of the numbers in projects.
+///
+/// Older versions did not keep them, but they are still in the paths
+/// of the folders and files.
+pub fn find_project_areas(system: &mut System) {
+    let layout = system.layout;
+    system.id = std::mem::take(&mut system.id)
+        .into_iter()
+        .map(|mut jd| {
+            if let (Some(_), Some(path)) = (jd.project, jd.path.path()) {
+                if let Ok(found) = JdNumber::from_path(path.to_path_buf(), layout) {
+                    jd.project_area_label = found.project_area_label;
+                }
+            }
+            jd
+        })
+        .collect();
+}
+
+/// Read an index in any known version, and upgrade it to the current one.
+///
+/// Also returns the version the index was written in.
+pub fn upgrade(text: &str) -> Result<(System, u32), String> {
+    let version = deserialize::<Probe>(text)?.version;
+
+    let system = match version {
+        0 => upgrade_v0(deserialize(text)?),
+        1 => upgrade_old::<v1::JdNumber>(deserialize(text)?),
+        2 => upgrade_old::<v2::JdNumber>(deserialize(text)?),
+        3 => upgrade_old::<JdNumber>(deserialize(text)?),
+        CURRENT_VERSION => deserialize(text)?,
+        _ => {
+            return Err(format!(
+                "Index has version {}, but this jd can only read up to version {}.",
+                version, CURRENT_VERSION
+            ))
+        }
+    };
+
+    Ok((system, version))
+}
+
+/// Deserialize RON, naming the field that could not be read if it fails.
+fn deserialize<T: DeserializeOwned>(text: &str) -> Result<T, String> {
+    let mut deserializer =
+        ron::Deserializer::from_str(text).map_err(|e| format!("Cannot read index file: {}", e))?;
+
+    let value = serde_path_to_error::deserialize(&mut deserializer).map_err(|e| {
+        let field = e.path().to_string();
+        let error = deserializer.span_error(e.into_inner());
+        if field == "." {
+            format!("Cannot read index file: {}", error)
+        } else {
+            format!("Cannot read index file: field `{}`: {}", field, error)
+        }
+    })?;
+    deserializer
+        .end()
+        .map_err(|e| format!("Cannot read index file: {}", deserializer.span_error(e)))?;
+
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{upgrade, CURRENT_VERSION};
+    use crate::system::System;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_upgrade_v0() {
+        // An index from before the version field, with the old `projects` field.
+        let text = r#"
+(path:"/home/calvin/jd",
+projects:[],
+id:[(project:None,category:12,id:1,label:"_sept_payroll",area_label:"_finance",category_label:"_payroll",path:Path("/home/calvin/jd/10-19_finance/12_payroll/12.01_sept_payroll"))
+])
+"#;
+        let (system, version) = upgrade(text).unwrap();
+
+        assert_eq!(version, 0);
+        assert_eq!(system.version, CURRENT_VERSION);
+        assert_eq!(system.id.len(), 1);
+        assert_eq!(system.area_labels.get(&10).unwrap(), "_finance");
+        assert_eq!(system.category_labels.get(&12).unwrap(), "_payroll");
+
+        // the upgraded index reads back as the current version
+        let text = ron::to_string(&system).unwrap();
+        assert_eq!(upgrade(&text).unwrap().1, CURRENT_VERSION);
+    }
+
+    #[test]
+    fn test_upgrade_v1() {
+        // Version 1 did not keep the project area of numbers in projects.
+        let text = r#"
+(version:1,
+path:"/home/calvin/jd",
+id:[(project:Some(101),project_label:Some("_website"),category:12,id:1,label:"_home_page",area_label:"_design",category_label:"_mockups",path:Path("/home/calvin/jd/100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page"))
+])
+"#;
+        let (system, version) = upgrade(text).unwrap();
+
+        assert_eq!(version, 1);
+        assert_eq!(system.version, CURRENT_VERSION);
+        let jd = system.id.iter().next().unwrap();
+        assert_eq!(jd.project_area_label, Some("_projects".to_string()));
+        assert_eq!(
+            system.get_path(jd),
+            PathBuf::from(
+                "/home/calvin/jd/100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page"
+            )
+        );
+    }
+
+    #[test]
+    fn test_upgrade_v2() {
+        // Version 2 had no extended ids.
+        let text = r#"
+(version:2,
+path:"/home/calvin/jd",
+id:[(project:None,project_label:None,project_area_label:None,category:12,id:1,label:"_sept_payroll",area_label:"_finance",category_label:"_payroll",path:Path("/home/calvin/jd/10-19_finance/12_payroll/12.01_sept_payroll"))
+])
+"#;
+        let (system, version) = upgrade(text).unwrap();
+
+        assert_eq!(version, 2);
+        assert_eq!(system.version, CURRENT_VERSION);
+        let jd = system.id.iter().next().unwrap();
+        assert!(!jd.extended);
+        assert_eq!(jd.to_string(), "12.01_sept_payroll");
+    }
+
+    #[test]
+    fn test_upgrade_v3() {
+        // Version 3 did not cache disk usage.
+        let text = r#"
+(version:3,
+path:"/home/calvin/jd",
+id:[(project:None,project_label:None,project_area_label:None,category:12,id:1,extended:true,label:"_sept_payroll",area_label:"_finance",category_label:"_payroll",path:Path("/home/calvin/jd/10-19_finance/12_payroll/12.001_sept_payroll"))
+])
+"#;
+        let (system, version) = upgrade(text).unwrap();
+
+        assert_eq!(version, 3);
+        assert_eq!(system.version, CURRENT_VERSION);
+        assert!(system.usage.is_empty());
+        assert_eq!(system.id.iter().next().unwrap().number(), "12.001");
+    }
+
+    #[test]
+    fn test_newer_version() {
+        let text = r#"(version:99,path:"/jd",id:[])"#;
+        assert!(upgrade(text).unwrap_err().contains("version 99"));
+    }
+
+    #[test]
+    fn test_names_bad_field() {
+        let text = r#"
+(version:1,
+path:"/home/calvin/jd",
+id:[(project:None,category:"twelve",id:1,label:"_sept_payroll",area_label:"_finance",category_label:"_payroll",path:Path("/home/calvin/jd/12.01_sept_payroll"))
+])
+"#;
+        let error = upgrade(text).unwrap_err();
+        assert!(error.contains("`id[0].category`"), "{}", error);
+        assert!(error.contains("4:"), "{}", error);
+
+        // fields that are left over from old versions are named too
+        let text = r#"(version:1,path:"/jd",projects:[],id:[])"#;
+        let error = upgrade(text).unwrap_err();
+        assert!(error.contains("projects"), "{}", error);
+    }
+
+    #[test]
+    fn test_current_version() {
+        let system = System::new("/jd".into());
+        let text = ron::to_string(&system).unwrap();
+        assert!(text.starts_with("(version:4,"));
+        assert_eq!(upgrade(&text).unwrap().0.path, system.path);
+    }
+}
@@ -0,0 +1,536 @@
+pub mod binary;
+pub mod migrate;
+pub mod registry;
+pub mod sqlite;
+pub mod text;
+
+use crate::jdnumber::JdNumber;
+use crate::system::System;
+use registry::Registry;
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// The name of the index file at the root of a system.
+pub const INDEX_FILE: &str = ".JdIndex";
+
+/// Find the index of the system `start` is in.
+///
+/// Each folder from `start` upwards is checked for a `.JdIndex`, and
+/// for an index that `registry` keeps outside of the system.
+pub fn find_index_from(start: &Path, registry: &Registry) -> Option<PathBuf> {
+    find_system_from(start, registry).map(|(_, index)| index)
+}
+
+/// Find the root folder and the index of the system `start` is in,
+/// like [`find_index_from`].
+pub fn find_system_from(start: &Path, registry: &Registry) -> Option<(PathBuf, PathBuf)> {
+    for dir in start.ancestors() {
+        let index = dir.join(INDEX_FILE);
+        if index.is_file() {
+            return Some((dir.to_path_buf(), index));
+        }
+        if let Some(index) = registry.index_for(dir) {
+            return Some((dir.to_path_buf(), index.to_path_buf()));
+        }
+    }
+    None
+}
+
+/// Find the index of the system the current directory is in.
+pub fn find_index() -> Option<PathBuf> {
+    find_index_for(&env::current_dir().ok()?)
+}
+
+/// Find the index of the system a path is in.
+///
+/// The path does not have to exist.
+pub fn find_index_for(path: &Path) -> Option<PathBuf> {
+    find_system_for(path).map(|(_, index)| index)
+}
+
+/// Find the root folder and the index of the system a path is in.
+///
+/// The index is not read, so this is fast.
+pub fn find_system_for(path: &Path) -> Option<(PathBuf, PathBuf)> {
+    // Systems are registered by their canonical path.
+    let start = path.canonicalize().unwrap_or(path.to_path_buf());
+    let registry = registry::data_dir()
+        .and_then(|dir| Registry::load(&dir).ok())
+        .unwrap_or_default();
+    find_system_from(&start, &registry)
+}
+
+/// A way of storing an index on disk.
+pub trait Storage {
+    /// Read an index, upgrading it to the current version.
+    ///
+    /// Also returns the version the index was written in.
+    fn read(&self, path: &Path) -> Result<(System, u32), String>;
+
+    /// Read an index with only the number `jd` in it.
+    ///
+    /// Backends that can find one number without reading the whole
+    /// index should override this.
+    fn read_id(&self, path: &Path, jd: &JdNumber) -> Result<System, String> {
+        let (mut system, _) = self.read(path)?;
+        system.id.retain(|other| other == jd);
+        Ok(system)
+    }
+
+    /// Write an index, replacing whatever was there.
+    ///
+    /// This must never leave a half written index behind; see `replace_file`.
+    fn write(&self, path: &Path, system: &System) -> Result<(), String>;
+}
+
+/// The storage backends an index can use.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Backend {
+    /// RON text; see `text::RonStorage`.
+    #[default]
+    Ron,
+    /// Bincode; see `binary::BinaryStorage`.
+    Binary,
+    /// An SQLite database; see `sqlite::SqliteStorage`.
+    Sqlite,
+}
+
+impl Backend {
+    /// Find out which backend an index file uses, from its first bytes.
+    ///
+    /// Files that do not exist or are not recognised are RON.
+    pub fn detect(path: &Path) -> Backend {
+        let mut start = Vec::new();
+        if let Ok(file) = fs::File::open(path) {
+            let _ = file.take(16).read_to_end(&mut start);
+        }
+
+        if start.starts_with(sqlite::MAGIC) {
+            Backend::Sqlite
+        } else if start.starts_with(binary::MAGIC) {
+            Backend::Binary
+        } else {
+            Backend::Ron
+        }
+    }
+
+    /// The file extension of an index kept outside its system.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Backend::Ron => "ron",
+            Backend::Binary => "bin",
+            Backend::Sqlite => "sqlite",
+        }
+    }
+
+    /// Get the storage for this backend.
+    pub fn storage(self) -> &'static dyn Storage {
+        match self {
+            Backend::Ron => &text::RonStorage,
+            Backend::Binary => &binary::BinaryStorage,
+            Backend::Sqlite => &sqlite::SqliteStorage,
+        }
+    }
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ron" => Ok(Backend::Ron),
+            "binary" => Ok(Backend::Binary),
+            "sqlite" => Ok(Backend::Sqlite),
+            _ => Err(String::from("unknown backend.  Use ron, binary or sqlite.")),
+        }
+    }
+}
+
+impl fmt::Display for Backend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Backend::Ron => write!(f, "ron"),
+            Backend::Binary => write!(f, "binary"),
+            Backend::Sqlite => write!(f, "sqlite"),
+        }
+    }
+}
+
+/// Read a system from an index file.
+///
+/// Indexes written by older versions of jd are upgraded, and the
+/// upgraded index is written back.
+pub fn read_index(path: &Path) -> Result<System, String> {
+    let (system, version) = Backend::detect(path).storage().read(path)?;
+    if version == migrate::CURRENT_VERSION {
+        return Ok(system);
+    }
+    IndexLock::acquire(path)?.read()
+}
+
+/// Read a system from an index file, with only the number `jd` in it.
+pub fn read_id(path: &Path, jd: &JdNumber) -> Result<System, String> {
+    Backend::detect(path).storage().read_id(path, jd)
+}
+
+/// Write a system to an index file, using `backend`.
+pub fn write_index_to(path: &Path, system: &System, backend: Backend) -> Result<(), String> {
+    backend.storage().write(path, system)
+}
+
+/// Replace a file with one written by `write`.
+///
+/// `write` is given a temporary file next to `path` to write, which is
+/// then renamed over `path`, so a crash never leaves a half written file.
+pub(crate) fn replace_file<F>(path: &Path, write: F) -> Result<(), String>
+where
+    F: FnOnce(&Path) -> Result<(), String>,
+{
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(format!(".tmp.{}", std::process::id()));
+    let temp = path.with_file_name(temp_name);
+    let _ = fs::remove_file(&temp);
+
+    let written = write(&temp)
+        .and_then(|_| fs::rename(&temp, path).map_err(|_| String::from("Cannot write to file.")));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+/// Write a file and make sure it is on disk.
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    fs::File::create(path)
+        .and_then(|mut file| {
+            file.write_all(bytes)?;
+            file.sync_all()
+        })
+        .map_err(|_| String::from("Cannot write to file."))
+}
+
+/// An advisory lock on an index file.
+///
+/// Hold the lock from reading the index until writing it back, so that
+/// two commands changing the same system at once do not overwrite each
+/// other.  The lock is released when this is dropped.
+pub struct IndexLock {
+    /// The index file the lock is for.
+    pub path: PathBuf,
+    _file: fs::File,
+}
+
+impl IndexLock {
+    /// Wait for and take the lock on an index file.
+    ///
+    /// The lock is taken on a separate `.lock` file, because writing
+    /// the index replaces the index file.
+    pub fn acquire(index: &Path) -> Result<IndexLock, &'static str> {
+        let mut lock_name = index.file_name().unwrap_or_default().to_os_string();
+        lock_name.push(".lock");
+
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(index.with_file_name(lock_name))
+            .map_err(|_| "Cannot lock index file.")?;
+        lock_file(&file)?;
+
+        Ok(IndexLock {
+            path: index.to_path_buf(),
+            _file: file,
+        })
+    }
+
+    /// Read the locked index.
+    ///
+    /// If the index was written by an older version of jd, the upgraded
+    /// index is written back.
+    pub fn read(&self) -> Result<System, String> {
+        let (system, version) = Backend::detect(&self.path).storage().read(&self.path)?;
+        if version != migrate::CURRENT_VERSION {
+            self.write(&system)?;
+        }
+        Ok(system)
+    }
+
+    /// Read an index while this lock is held.
+    ///
+    /// If `path` is the locked index, however it is written, it is read
+    /// through this lock, as upgrading it would otherwise wait forever for
+    /// the lock this already holds.  Other indexes are read with [`read_index`].
+    pub fn read_index(&self, path: &Path) -> Result<System, String> {
+        if same_file(path, &self.path) {
+            self.read()
+        } else {
+            read_index(path)
+        }
+    }
+
+    /// Write the locked index, keeping the backend it already uses.
+    pub fn write(&self, system: &System) -> Result<(), String> {
+        self.write_as(system, Backend::detect(&self.path))
+    }
+
+    /// Write the locked index using `backend`.
+    pub fn write_as(&self, system: &System, backend: Backend) -> Result<(), String> {
+        write_index_to(&self.path, system, backend)
+    }
+}
+
+/// Check if two paths are the same file, like `a/.JdIndex` and `./a/.JdIndex`.
+///
+/// Files that do not exist are not the same as anything.
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (fs::canonicalize(a), fs::canonicalize(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+/// Take an exclusive lock on a file, waiting until it is free.
+///
+/// The lock is released when the file is closed.
+#[cfg(unix)]
+fn lock_file(file: &fs::File) -> Result<(), &'static str> {
+    use std::os::unix::io::AsRawFd;
+
+    loop {
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } == 0 {
+            return Ok(());
+        }
+        if std::io::Error::last_os_error().kind() != std::io::ErrorKind::Interrupted {
+            return Err("Cannot lock index file.");
+        }
+    }
+}
+
+/// Take an exclusive lock on a file.
+///
+/// Locking is only supported on unix; elsewhere this does nothing.
+#[cfg(not(unix))]
+fn lock_file(_file: &fs::File) -> Result<(), &'static str> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::registry::{Entry, Registry};
+    use super::{find_index_from, read_index, write_index_to, Backend, IndexLock, INDEX_FILE};
+    use crate::system::System;
+    use crate::testing::TempRoot;
+    use std::fs;
+    use std::path::PathBuf;
+    use std::thread;
+
+    #[test]
+    fn test_write_and_find_index() {
+        let root = TempRoot::new("storage_write");
+        let index = root.join(INDEX_FILE);
+        let system = System::new(root.to_path_buf());
+
+        write_index_to(&index, &system, Backend::Ron).unwrap();
+
+        let inner = root.join("10-19_finance/12_payroll");
+        fs::create_dir_all(&inner).unwrap();
+        assert_eq!(
+            find_index_from(&inner, &Registry::default()),
+            Some(index.clone())
+        );
+        assert_eq!(read_index(&index).unwrap().path, root.to_path_buf());
+
+        // no temporary file is left behind
+        let names: Vec<_> = fs::read_dir(&root)
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert!(!names.iter().any(|n| n.to_string_lossy().contains(".tmp")));
+    }
+
+    #[test]
+    fn test_find_external_index() {
+        let root = TempRoot::new("storage_external");
+        let inner = root.join("10-19_finance/12_payroll");
+        fs::create_dir_all(&inner).unwrap();
+
+        let registry = Registry {
+            systems: vec![Entry {
+                root: root.to_path_buf(),
+                index: PathBuf::from("/data/jd/jd.ron"),
+            }],
+        };
+        assert_eq!(
+            find_index_from(&inner, &registry),
+            Some(PathBuf::from("/data/jd/jd.ron"))
+        );
+        assert_eq!(find_index_from(&inner, &Registry::default()), None);
+
+        // an index inside a folder wins over one further up
+        let nested = root.join("10-19_finance");
+        write_index_to(
+            &nested.join(INDEX_FILE),
+            &System::new(nested.clone()),
+            Backend::Ron,
+        )
+        .unwrap();
+        assert_eq!(
+            find_index_from(&inner, &registry),
+            Some(nested.join(INDEX_FILE))
+        );
+    }
+
+    #[test]
+    fn test_backends_round_trip() {
+        let root = TempRoot::new("storage_backends");
+        let index = root.join(INDEX_FILE);
+
+        let mut system = System::new(root.to_path_buf());
+        system.layout = crate::jdnumber::Layout::NoArea;
+        system.area_labels.insert(10, String::from("_finance"));
+        let mut jd = crate::jdnumber::JdNumber::try_from(PathBuf::from(
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+        ))
+        .unwrap();
+        jd.locations.push(crate::jdnumber::Location::Physical {
+            description: String::from("Box 3"),
+        });
+        system.add_id(jd).unwrap();
+        system.usage.insert(
+            String::from("12.01"),
+            crate::usage::Usage {
+                bytes: 2048,
+                files: 3,
+                modified: std::time::UNIX_EPOCH
+                    + std::time::Duration::new(1_700_000_000, 123_456_789),
+            },
+        );
+
+        for backend in [Backend::Ron, Backend::Binary, Backend::Sqlite] {
+            write_index_to(&index, &system, backend).unwrap();
+            assert_eq!(Backend::detect(&index), backend);
+
+            let read = read_index(&index).unwrap();
+            assert_eq!(read.path, system.path);
+            assert_eq!(read.layout, system.layout);
+            assert_eq!(read.area_labels, system.area_labels);
+            assert_eq!(read.usage, system.usage);
+            assert_eq!(read.id.len(), 1);
+            assert!(crate::jdnumber::JdNumber::check_exactly_equal(
+                read.id.iter().next().unwrap().clone(),
+                system.id.iter().next().unwrap().clone()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_backend_from_str() {
+        assert_eq!("sqlite".parse::<Backend>(), Ok(Backend::Sqlite));
+        assert_eq!(
+            Backend::Binary.to_string().parse::<Backend>(),
+            Ok(Backend::Binary)
+        );
+        assert!("json".parse::<Backend>().is_err());
+    }
+
+    #[test]
+    fn test_upgrades_on_read() {
+        let root = TempRoot::new("storage_upgrade");
+        let index = root.join(INDEX_FILE);
+        fs::write(
+            &index,
+            format!("(path:{:?},projects:[],id:[])", root.to_path_buf()),
+        )
+        .unwrap();
+
+        let system = read_index(&index).unwrap();
+        assert_eq!(system.version, super::migrate::CURRENT_VERSION);
+        let text = fs::read_to_string(&index).unwrap();
+        assert!(text.contains(&format!("version: {}", super::migrate::CURRENT_VERSION)));
+    }
+
+    #[test]
+    fn test_merge_into_old_index() {
+        let root = TempRoot::new("storage_merge_old");
+        let a = root.join("a").join(INDEX_FILE);
+        let b = root.join("b").join(INDEX_FILE);
+        fs::create_dir_all(a.parent().unwrap()).unwrap();
+        fs::create_dir_all(b.parent().unwrap()).unwrap();
+
+        let mut system = System::new(root.to_path_buf());
+        system
+            .add_id(crate::jdnumber::JdNumber::try_from(String::from("12.01")).unwrap())
+            .unwrap();
+        write_index_to(&a, &system, Backend::Ron).unwrap();
+        // an index from before versions were written
+        fs::write(
+            &b,
+            format!("(path:{:?},projects:[],id:[])", root.to_path_buf()),
+        )
+        .unwrap();
+
+        // Merge into b, locked by another name, like `jd index merge a ./b -o b`.
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let (a_path, b_path) = (a.clone(), b.clone());
+        thread::spawn(move || {
+            let lock = IndexLock::acquire(&b_path).unwrap();
+            let spelled = b_path.parent().unwrap().join(".").join(INDEX_FILE);
+            let a_system = lock.read_index(&a_path).unwrap();
+            let b_system = lock.read_index(&spelled).unwrap();
+            let merged = crate::merge::merge(crate::merge::Base::Disk, a_system, b_system);
+            let (system, _) = merged.resolve(&[]).unwrap();
+            lock.write(&system).unwrap();
+            sender.send(()).unwrap();
+        });
+        receiver
+            .recv_timeout(std::time::Duration::from_secs(10))
+            .expect("Merging into an old index waited for its own lock.");
+
+        let merged = read_index(&b).unwrap();
+        assert_eq!(merged.version, super::migrate::CURRENT_VERSION);
+        assert_eq!(merged.id.len(), 1);
+    }
+
+    #[test]
+    fn test_concurrent_adds() {
+        let root = TempRoot::new("storage_concurrent");
+        let index = root.join(INDEX_FILE);
+
+        let mut system = System::new(root.to_path_buf());
+        system
+            .add_id(
+                crate::jdnumber::JdNumber::try_from(PathBuf::from(
+                    "10-19_finance/12_payroll/12.01_sept_payroll",
+                ))
+                .unwrap(),
+            )
+            .unwrap();
+        write_index_to(&index, &system, Backend::Ron).unwrap();
+
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let index = index.clone();
+                thread::spawn(move || {
+                    let lock = IndexLock::acquire(&index).unwrap();
+                    let mut system = lock.read().unwrap();
+                    system
+                        .add_id_from_str("12".to_string(), format!("_thread_{}", i))
+                        .unwrap();
+                    lock.write(&system).unwrap();
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+
+        let system = read_index(&index).unwrap();
+        let mut ids: Vec<u32> = system.id.iter().map(|jd| jd.id).collect();
+        ids.dedup();
+        assert_eq!(ids, (1..=9).collect::<Vec<u32>>());
+    }
+}
@@ -0,0 +1,162 @@
+use super::{replace_file, write_synced, IndexLock};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name of the registry file in the data directory.
+pub const REGISTRY_FILE: &str = "systems.ron";
+
+/// The directory indexes kept outside their systems are stored in.
+///
+/// This is `$XDG_DATA_HOME/jd`, or `~/.local/share/jd` if that is not set.
+pub fn data_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_DATA_HOME") {
+        Some(dir) if Path::new(&dir).is_absolute() => PathBuf::from(dir),
+        _ => PathBuf::from(env::var_os("HOME")?).join(".local/share"),
+    };
+    Some(base.join("jd"))
+}
+
+/// A system whose index is kept outside of it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    /// The root folder of the system.
+    #[serde(with = "crate::jdnumber::lossless_path")]
+    pub root: PathBuf,
+    /// The index file of the system.
+    #[serde(with = "crate::jdnumber::lossless_path")]
+    pub index: PathBuf,
+}
+
+/// The systems whose indexes are kept in the data directory.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Registry {
+    pub systems: Vec<Entry>,
+}
+
+impl Registry {
+    /// Read the registry in `dir`.
+    ///
+    /// If there is no registry yet, it is empty.
+    pub fn load(dir: &Path) -> Result<Registry, String> {
+        let path = dir.join(REGISTRY_FILE);
+        if !path.exists() {
+            return Ok(Registry::default());
+        }
+        let text = fs::read_to_string(&path).map_err(|_| "Cannot read system registry.")?;
+        ron::from_str(&text).map_err(|e| format!("Cannot read system registry: {}", e))
+    }
+
+    /// Write the registry to `dir`.
+    pub fn save(&self, dir: &Path) -> Result<(), String> {
+        fs::create_dir_all(dir).map_err(|_| "Cannot create data directory.")?;
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::new())
+            .map_err(|_| "Cannot write system registry.")?;
+        replace_file(&dir.join(REGISTRY_FILE), |temp| {
+            write_synced(temp, text.as_bytes())
+        })
+    }
+
+    /// Change the registry in `dir`, holding a lock on it while doing so.
+    pub fn update<T, F>(dir: &Path, change: F) -> Result<T, String>
+    where
+        F: FnOnce(&mut Registry) -> T,
+    {
+        fs::create_dir_all(dir).map_err(|_| "Cannot create data directory.")?;
+        let _lock = IndexLock::acquire(&dir.join(REGISTRY_FILE))?;
+        let mut registry = Registry::load(dir)?;
+        let result = change(&mut registry);
+        registry.save(dir)?;
+        Ok(result)
+    }
+
+    /// Get the index of the system at `root`, if it is kept outside of it.
+    pub fn index_for(&self, root: &Path) -> Option<&Path> {
+        self.systems
+            .iter()
+            .find(|entry| entry.root == root)
+            .map(|entry| entry.index.as_path())
+    }
+
+    /// Keep the index of the system at `root` in `dir`.
+    ///
+    /// The index is named after the root folder, for example
+    /// `jd.ron`, with a number added if that name is taken.
+    /// Returns the path of the index.
+    pub fn register(&mut self, root: &Path, dir: &Path, extension: &str) -> PathBuf {
+        if let Some(index) = self.index_for(root) {
+            return index.to_path_buf();
+        }
+
+        let name = root
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_else(|| String::from("root"));
+
+        let mut index = dir.join(format!("{}.{}", name, extension));
+        let mut n = 2;
+        while index.exists() || self.systems.iter().any(|entry| entry.index == index) {
+            index = dir.join(format!("{}-{}.{}", name, n, extension));
+            n += 1;
+        }
+
+        self.systems.push(Entry {
+            root: root.to_path_buf(),
+            index: index.clone(),
+        });
+        index
+    }
+
+    /// Stop keeping the index of the system at `root` outside of it.
+    ///
+    /// Returns the path the index was kept at.
+    pub fn unregister(&mut self, root: &Path) -> Option<PathBuf> {
+        let position = self.systems.iter().position(|entry| entry.root == root)?;
+        Some(self.systems.remove(position).index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Registry;
+    use crate::testing::TempRoot;
+    use std::path::{Path, PathBuf};
+
+    #[test]
+    fn test_register() {
+        let dir = TempRoot::new("registry");
+
+        let index = Registry::update(&dir, |registry| {
+            registry.register(Path::new("/home/calvin/jd"), &dir, "ron")
+        })
+        .unwrap();
+        assert_eq!(index, dir.join("jd.ron"));
+
+        // another system with the same name gets another file
+        let other = Registry::update(&dir, |registry| {
+            registry.register(Path::new("/mnt/work/jd"), &dir, "ron")
+        })
+        .unwrap();
+        assert_eq!(other, dir.join("jd-2.ron"));
+
+        let registry = Registry::load(&dir).unwrap();
+        assert_eq!(
+            registry.index_for(Path::new("/home/calvin/jd")),
+            Some(index.as_path())
+        );
+        assert_eq!(registry.index_for(Path::new("/home/calvin")), None);
+
+        // registering again keeps the same file
+        let mut registry = registry;
+        assert_eq!(
+            registry.register(Path::new("/mnt/work/jd"), &dir, "ron"),
+            other
+        );
+        assert_eq!(
+            registry.unregister(Path::new("/mnt/work/jd")),
+            Some(PathBuf::from(&other))
+        );
+        assert_eq!(registry.index_for(Path::new("/mnt/work/jd")), None);
+    }
+}
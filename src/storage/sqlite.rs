@@ -0,0 +1,348 @@
+use super::{migrate, replace_file, Storage};
+use crate::jdnumber::{lossless_path, JdNumber, Location};
+use crate::system::System;
+use crate::usage::Usage;
+use rusqlite::{params, Connection, OpenFlags, Params};
+use std::path::Path;
+use std::time::{Duration, UNIX_EPOCH};
+
+/// The first bytes of every SQLite database.
+pub const MAGIC: &[u8] = b"SQLite format 3\0";
+
+/// The tables of an SQLite index.
+///
+/// Locations and the layout are kept as RON text, like in a RON index.
+const SCHEMA: &str = "
+CREATE TABLE system (version INTEGER NOT NULL, path BLOB NOT NULL, layout TEXT NOT NULL);
+CREATE TABLE area_labels (area INTEGER PRIMARY KEY, label TEXT NOT NULL);
+CREATE TABLE category_labels (category INTEGER PRIMARY KEY, label TEXT NOT NULL);
+CREATE TABLE ids (
+    project INTEGER,
+    project_label TEXT,
+    project_area_label TEXT,
+    category INTEGER NOT NULL,
+    id INTEGER NOT NULL,
+    extended INTEGER NOT NULL,
+    label TEXT NOT NULL,
+    area_label TEXT NOT NULL,
+    category_label TEXT NOT NULL,
+    path TEXT NOT NULL,
+    locations TEXT NOT NULL
+);
+CREATE INDEX ids_number ON ids (category, id);
+CREATE TABLE usage (
+    number TEXT PRIMARY KEY,
+    bytes INTEGER NOT NULL,
+    files INTEGER NOT NULL,
+    modified_secs INTEGER NOT NULL,
+    modified_nanos INTEGER NOT NULL
+);
+";
+
+/// Stores the index in an SQLite database.
+///
+/// Looking up one number only reads that number, so this is the best
+/// backend for very large systems.
+pub struct SqliteStorage;
+
+fn read_error(error: rusqlite::Error) -> String {
+    format!("Cannot read index file: {}", error)
+}
+
+fn write_error(error: rusqlite::Error) -> String {
+    format!("Cannot write index file: {}", error)
+}
+
+/// Open an index for reading.
+fn open(path: &Path) -> Result<Connection, String> {
+    Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY).map_err(read_error)
+}
+
+/// Read everything about a system but its numbers.
+///
+/// Also returns the version the index was written in.
+fn read_system(connection: &Connection) -> Result<(System, u32), String> {
+    let (version, path, layout): (u32, Vec<u8>, String) = connection
+        .query_row("SELECT version, path, layout FROM system", [], |row| {
+            Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+        })
+        .map_err(read_error)?;
+
+    // SQLite indexes were first written in version 1.
+    if !(1..=migrate::CURRENT_VERSION).contains(&version) {
+        return Err(format!(
+            "SQLite index has version {}, but this jd can only read versions 1 to {}.",
+            version,
+            migrate::CURRENT_VERSION
+        ));
+    }
+
+    let mut system = System::new(lossless_path::from_bytes(path));
+    system.layout =
+        ron::from_str(&layout).map_err(|e| format!("Cannot read index file: layout: {}", e))?;
+
+    for (table, labels) in [
+        ("area_labels", &mut system.area_labels),
+        ("category_labels", &mut system.category_labels),
+    ] {
+        let mut statement = connection
+            .prepare(&format!("SELECT * FROM {}", table))
+            .map_err(read_error)?;
+        let rows = statement
+            .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+            .map_err(read_error)?;
+        for row in rows {
+            let (number, label) = row.map_err(read_error)?;
+            labels.insert(number, label);
+        }
+    }
+
+    // Disk usage was first cached in version 4.
+    if version >= 4 {
+        let mut statement = connection
+            .prepare("SELECT * FROM usage")
+            .map_err(read_error)?;
+        let rows = statement
+            .query_map([], |row| {
+                Ok((
+                    row.get(0)?,
+                    Usage {
+                        bytes: row.get(1)?,
+                        files: row.get(2)?,
+                        modified: UNIX_EPOCH + Duration::new(row.get(3)?, row.get(4)?),
+                    },
+                ))
+            })
+            .map_err(read_error)?;
+        for row in rows {
+            let (number, usage) = row.map_err(read_error)?;
+            system.usage.insert(number, usage);
+        }
+    }
+
+    Ok((system, version))
+}
+
+/// Read the numbers matching an SQL condition from an index written in `version`.
+fn read_ids<P: Params>(
+    connection: &Connection,
+    version: u32,
+    condition: &str,
+    params: P,
+) -> Result<Vec<JdNumber>, String> {
+    // Version 1 did not keep project areas, and ids had two digits before version 3.
+    let project_area_label = if version >= 2 {
+        "project_area_label"
+    } else {
+        "NULL"
+    };
+    let extended = if version >= 3 { "extended" } else { "0" };
+    let mut statement = connection
+        .prepare(&format!(
+            "SELECT project, project_label, {}, category, id, {}, label, area_label, category_label, path, locations
+             FROM ids WHERE {} ORDER BY project, category, id",
+            project_area_label, extended, condition
+        ))
+        .map_err(read_error)?;
+    let rows = statement
+        .query_map(params, |row| {
+            Ok((
+                JdNumber {
+                    project: row.get(0)?,
+                    project_label: row.get(1)?,
+                    project_area_label: row.get(2)?,
+                    category: row.get(3)?,
+                    id: row.get(4)?,
+                    extended: row.get(5)?,
+                    label: row.get(6)?,
+                    area_label: row.get(7)?,
+                    category_label: row.get(8)?,
+                    path: Location::Url(String::new()),
+                    locations: Vec::new(),
+                },
+                row.get::<_, String>(9)?,
+                row.get::<_, String>(10)?,
+            ))
+        })
+        .map_err(read_error)?;
+
+    let mut ids = Vec::new();
+    for row in rows {
+        let (mut jd, path, locations) = row.map_err(read_error)?;
+        jd.path = ron::from_str(&path)
+            .map_err(|e| format!("Cannot read index file: path of {}: {}", jd, e))?;
+        jd.locations = ron::from_str(&locations)
+            .map_err(|e| format!("Cannot read index file: locations of {}: {}", jd, e))?;
+        ids.push(jd);
+    }
+    Ok(ids)
+}
+
+/// Write a whole system to a new database.
+fn write_system(connection: &mut Connection, system: &System) -> Result<(), String> {
+    connection.execute_batch(SCHEMA).map_err(write_error)?;
+    let transaction = connection.transaction().map_err(write_error)?;
+
+    let layout = ron::to_string(&system.layout).map_err(|_| "Cannot write index file.")?;
+    transaction
+        .execute(
+            "INSERT INTO system VALUES (?1, ?2, ?3)",
+            params![
+                system.version,
+                lossless_path::to_bytes(&system.path),
+                layout
+            ],
+        )
+        .map_err(write_error)?;
+
+    for (table, labels) in [
+        ("area_labels", &system.area_labels),
+        ("category_labels", &system.category_labels),
+    ] {
+        let mut statement = transaction
+            .prepare(&format!("INSERT INTO {} VALUES (?1, ?2)", table))
+            .map_err(write_error)?;
+        for (number, label) in labels {
+            statement
+                .execute(params![number, label])
+                .map_err(write_error)?;
+        }
+    }
+
+    {
+        let mut statement = transaction
+            .prepare("INSERT INTO usage VALUES (?1, ?2, ?3, ?4, ?5)")
+            .map_err(write_error)?;
+        for (number, usage) in &system.usage {
+            // A time before 1970 is written as 1970, so that cache is never used.
+            let modified = usage
+                .modified
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default();
+            statement
+                .execute(params![
+                    number,
+                    usage.bytes,
+                    usage.files,
+                    modified.as_secs(),
+                    modified.subsec_nanos()
+                ])
+                .map_err(write_error)?;
+        }
+    }
+
+    {
+        let mut statement = transaction
+            .prepare("INSERT INTO ids VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)")
+            .map_err(write_error)?;
+        for jd in &system.id {
+            let path = ron::to_string(&jd.path).map_err(|_| "Cannot write index file.")?;
+            let locations =
+                ron::to_string(&jd.locations).map_err(|_| "Cannot write index file.")?;
+            statement
+                .execute(params![
+                    jd.project,
+                    jd.project_label,
+                    jd.project_area_label,
+                    jd.category,
+                    jd.id,
+                    jd.extended,
+                    jd.label,
+                    jd.area_label,
+                    jd.category_label,
+                    path,
+                    locations
+                ])
+                .map_err(write_error)?;
+        }
+    }
+
+    transaction.commit().map_err(write_error)
+}
+
+impl Storage for SqliteStorage {
+    fn read(&self, path: &Path) -> Result<(System, u32), String> {
+        let connection = open(path)?;
+        let (mut system, version) = read_system(&connection)?;
+        system.id = read_ids(&connection, version, "1", [])?.into();
+        if version == 1 {
+            migrate::find_project_areas(&mut system);
+        }
+        Ok((system, version))
+    }
+
+    fn read_id(&self, path: &Path, jd: &JdNumber) -> Result<System, String> {
+        let connection = open(path)?;
+        let (mut system, version) = read_system(&connection)?;
+        system.id = read_ids(
+            &connection,
+            version,
+            "project IS ?1 AND category = ?2 AND id = ?3",
+            params![jd.project, jd.category, jd.id],
+        )?
+        .into();
+        if version == 1 {
+            migrate::find_project_areas(&mut system);
+        }
+        Ok(system)
+    }
+
+    fn write(&self, path: &Path, system: &System) -> Result<(), String> {
+        replace_file(path, |temp| {
+            let mut connection = Connection::open(temp).map_err(write_error)?;
+            write_system(&mut connection, system)?;
+            connection.close().map_err(|(_, e)| write_error(e))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SqliteStorage;
+    use crate::jdnumber::JdNumber;
+    use crate::storage::Storage;
+    use crate::system::System;
+    use crate::testing::TempRoot;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_read_id() {
+        let root = TempRoot::new("sqlite_read_id");
+        let path = root.join("index");
+
+        let mut system = System::new(PathBuf::from("/jd"));
+        for jd in [
+            "10-19_finance/12_payroll/12.01_sept_payroll",
+            "10-19_finance/12_payroll/12.02_oct_payroll",
+        ] {
+            system
+                .add_id(JdNumber::try_from(PathBuf::from(jd)).unwrap())
+                .unwrap();
+        }
+        let project = JdNumber::new(
+            "_design",
+            "_logos",
+            12,
+            1,
+            Some(101),
+            Some(String::from("_website")),
+            String::from("_draft"),
+            PathBuf::new(),
+        )
+        .unwrap();
+        system.add_id(project).unwrap();
+        SqliteStorage.write(&path, &system).unwrap();
+
+        let wanted = JdNumber::try_from(String::from("12.01")).unwrap();
+        let read = SqliteStorage.read_id(&path, &wanted).unwrap();
+        assert_eq!(read.path, PathBuf::from("/jd"));
+        assert_eq!(read.id.len(), 1);
+        assert_eq!(read.id.iter().next().unwrap().label, "_sept_payroll");
+        assert_eq!(read.id.iter().next().unwrap().project, None);
+
+        let wanted = JdNumber::try_from(String::from("101.12.01")).unwrap();
+        let read = SqliteStorage.read_id(&path, &wanted).unwrap();
+        assert_eq!(read.id.len(), 1);
+        assert_eq!(read.id.iter().next().unwrap().label, "_draft");
+    }
+}
@@ -0,0 +1,24 @@
+use super::{migrate, replace_file, Storage};
+use crate::system::System;
+use std::fs;
+use std::path::Path;
+
+/// Stores the index as RON text.
+///
+/// This is the original format of the index, and the easiest to read
+/// and edit by hand.  It is the only format older versions of jd wrote,
+/// so it is the only one that is upgraded from older versions.
+pub struct RonStorage;
+
+impl Storage for RonStorage {
+    fn read(&self, path: &Path) -> Result<(System, u32), String> {
+        let text = fs::read_to_string(path).map_err(|_| "Cannot read index file.")?;
+        migrate::upgrade(&text)
+    }
+
+    fn write(&self, path: &Path, system: &System) -> Result<(), String> {
+        let text = ron::ser::to_string_pretty(system, ron::ser::PrettyConfig::new())
+            .map_err(|_| "Cannot write index file.")?;
+        replace_file(path, |temp| super::write_synced(temp, text.as_bytes()))
+    }
+}
@@ -0,0 +1,363 @@
+use crate::jdnumber::JdNumber;
+use serde::{Deserialize, Serialize, Serializer};
+use std::collections::btree_map::{IntoValues, Values};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::RangeInclusive;
+use std::sync::OnceLock;
+
+/// The project, category and id of a JD number.
+type Key = (Option<u32>, u32, u32);
+
+fn key(jd: &JdNumber) -> Key {
+    (jd.project, jd.category, jd.id)
+}
+
+/// The JD numbers of a system.
+///
+/// The numbers are kept in order of project, category and id, the same
+/// order as `JdNumber`'s `Ord`.  The numbers of a project or of a category
+/// are a range of that order, so finding them does not look at any other
+/// numbers.  Numbers can also be found by the words in their labels; that
+/// index is built the first time it is used.
+///
+/// In an index file this is written as a list of numbers.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(from = "Vec<JdNumber>")]
+pub struct Ids {
+    numbers: BTreeMap<Key, JdNumber>,
+    /// The numbers with each word, in lowercase, in their labels.
+    words: OnceLock<BTreeMap<String, BTreeSet<Key>>>,
+}
+
+/// Split a label into lowercase words.
+fn words(label: &str) -> impl Iterator<Item = String> + '_ {
+    label
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+}
+
+impl Ids {
+    /// Get the number of JD numbers.
+    pub fn len(&self) -> usize {
+        self.numbers.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.numbers.is_empty()
+    }
+
+    /// Iterate over the JD numbers in order.
+    pub fn iter(&self) -> Values<'_, Key, JdNumber> {
+        self.numbers.values()
+    }
+
+    /// Get the JD number with the same project, category and id as `jd`.
+    pub fn get(&self, jd: &JdNumber) -> Option<&JdNumber> {
+        self.numbers.get(&key(jd))
+    }
+
+    /// Check if there is a JD number with the same project, category and id as `jd`.
+    pub fn contains(&self, jd: &JdNumber) -> bool {
+        self.numbers.contains_key(&key(jd))
+    }
+
+    /// Add a JD number, replacing and returning the one with the same number.
+    pub fn insert(&mut self, jd: JdNumber) -> Option<JdNumber> {
+        let key = key(&jd);
+        if let Some(index) = self.words.get_mut() {
+            for word in words(&jd.label) {
+                index.entry(word).or_default().insert(key);
+            }
+        }
+        let old = self.numbers.insert(key, jd);
+        if let Some(old) = &old {
+            self.unindex_words(old);
+        }
+        old
+    }
+
+    /// Remove and return the JD number with the same number as `jd`.
+    pub fn remove(&mut self, jd: &JdNumber) -> Option<JdNumber> {
+        let old = self.numbers.remove(&key(jd))?;
+        self.unindex_words(&old);
+        Some(old)
+    }
+
+    /// Keep only the JD numbers for which `keep` returns true.
+    pub fn retain<F: FnMut(&JdNumber) -> bool>(&mut self, mut keep: F) {
+        let removed: Vec<JdNumber> = self
+            .numbers
+            .values()
+            .filter(|jd| !keep(jd))
+            .cloned()
+            .collect();
+        for jd in removed {
+            self.remove(&jd);
+        }
+    }
+
+    /// Drop a number that was replaced or removed from the word index,
+    /// keeping the words of whatever now has its number.
+    fn unindex_words(&mut self, old: &JdNumber) {
+        let Some(index) = self.words.get_mut() else {
+            return;
+        };
+        let key = key(old);
+        let kept: BTreeSet<String> = match self.numbers.get(&key) {
+            Some(jd) => words(&jd.label).collect(),
+            None => BTreeSet::new(),
+        };
+        for word in words(&old.label).filter(|word| !kept.contains(word)) {
+            if let Some(keys) = index.get_mut(&word) {
+                keys.remove(&key);
+                if keys.is_empty() {
+                    index.remove(&word);
+                }
+            }
+        }
+    }
+
+    /// Iterate over the JD numbers of a project in order.
+    ///
+    /// A project of `None` gives the numbers that are not in a project.
+    pub fn in_project(
+        &self,
+        project: Option<u32>,
+    ) -> impl DoubleEndedIterator<Item = &JdNumber> + '_ {
+        self.in_range(project, 0..=u32::MAX)
+    }
+
+    /// Iterate over the JD numbers of a category in order.
+    pub fn in_category(
+        &self,
+        project: Option<u32>,
+        category: u32,
+    ) -> impl DoubleEndedIterator<Item = &JdNumber> + '_ {
+        self.in_range(project, category..=category)
+    }
+
+    /// Iterate over the JD numbers of an area in order.
+    ///
+    /// The area is given by its first category, like 10 for `10-19`.
+    pub fn in_area(
+        &self,
+        project: Option<u32>,
+        area: u32,
+    ) -> impl DoubleEndedIterator<Item = &JdNumber> + '_ {
+        self.in_range(project, area..=area + 9)
+    }
+
+    fn in_range(
+        &self,
+        project: Option<u32>,
+        categories: RangeInclusive<u32>,
+    ) -> impl DoubleEndedIterator<Item = &JdNumber> + '_ {
+        let (first, last) = categories.into_inner();
+        self.numbers
+            .range((project, first, 0)..=(project, last, u32::MAX))
+            .map(|(_, jd)| jd)
+    }
+
+    /// Find the JD numbers with a word in their label that starts with
+    /// `prefix`, ignoring case.
+    pub fn with_word(&self, prefix: &str) -> Vec<&JdNumber> {
+        let index = self.words.get_or_init(|| {
+            let mut index: BTreeMap<String, BTreeSet<Key>> = BTreeMap::new();
+            for (key, jd) in &self.numbers {
+                for word in words(&jd.label) {
+                    index.entry(word).or_default().insert(*key);
+                }
+            }
+            index
+        });
+
+        let prefix = prefix.to_lowercase();
+        let keys: BTreeSet<&Key> = index
+            .range(prefix.clone()..)
+            .take_while(|(word, _)| word.starts_with(&prefix))
+            .flat_map(|(_, keys)| keys)
+            .collect();
+        keys.into_iter().map(|key| &self.numbers[key]).collect()
+    }
+}
+
+impl PartialEq for Ids {
+    fn eq(&self, other: &Self) -> bool {
+        self.numbers == other.numbers
+    }
+}
+
+/// If a number is in the list twice, the last one is kept.
+impl From<Vec<JdNumber>> for Ids {
+    fn from(list: Vec<JdNumber>) -> Self {
+        list.into_iter().collect()
+    }
+}
+
+impl FromIterator<JdNumber> for Ids {
+    fn from_iter<T: IntoIterator<Item = JdNumber>>(iter: T) -> Self {
+        Ids {
+            numbers: iter.into_iter().map(|jd| (key(&jd), jd)).collect(),
+            words: OnceLock::new(),
+        }
+    }
+}
+
+impl Serialize for Ids {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.numbers.values())
+    }
+}
+
+impl IntoIterator for Ids {
+    type Item = JdNumber;
+    type IntoIter = IntoValues<Key, JdNumber>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.numbers.into_values()
+    }
+}
+
+impl<'a> IntoIterator for &'a Ids {
+    type Item = &'a JdNumber;
+    type IntoIter = Values<'a, Key, JdNumber>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.numbers.values()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Ids;
+    use crate::jdnumber::JdNumber;
+    use std::path::PathBuf;
+    use std::time::Instant;
+
+    fn jd(project: Option<u32>, category: u32, id: u32, label: &str) -> JdNumber {
+        JdNumber::new(
+            "_area",
+            "_category",
+            category,
+            id,
+            project,
+            None,
+            label.to_string(),
+            PathBuf::new(),
+        )
+        .unwrap()
+    }
+
+    fn numbers<'a>(list: impl IntoIterator<Item = &'a JdNumber>) -> Vec<String> {
+        list.into_iter().map(|jd| jd.to_string()).collect()
+    }
+
+    #[test]
+    fn test_ids() {
+        let mut ids = Ids::default();
+        ids.insert(jd(None, 22, 1, "_cleaning_contract"));
+        ids.insert(jd(Some(101), 12, 1, "_draft"));
+        ids.insert(jd(None, 12, 2, "_oct_payroll"));
+        ids.insert(jd(None, 12, 1, "_sept_payroll"));
+
+        assert_eq!(
+            numbers(&ids),
+            [
+                "12.01_sept_payroll",
+                "12.02_oct_payroll",
+                "22.01_cleaning_contract",
+                "101.12.01_draft"
+            ]
+        );
+        assert_eq!(
+            numbers(ids.in_category(None, 12)),
+            ["12.01_sept_payroll", "12.02_oct_payroll"]
+        );
+        assert_eq!(numbers(ids.in_project(Some(101))), ["101.12.01_draft"]);
+        assert_eq!(ids.get(&jd(None, 12, 2, "")).unwrap().label, "_oct_payroll");
+
+        assert_eq!(
+            numbers(ids.with_word("PAY")),
+            ["12.01_sept_payroll", "12.02_oct_payroll"]
+        );
+        // the word index follows changes
+        ids.insert(jd(None, 12, 2, "_oct_bonus"));
+        ids.remove(&jd(None, 12, 1, ""));
+        assert!(ids.with_word("payroll").is_empty());
+        assert_eq!(numbers(ids.with_word("oct")), ["12.02_oct_bonus"]);
+        ids.retain(|jd| jd.category != 12);
+        assert!(ids.with_word("oct").is_empty());
+        assert_eq!(numbers(&ids), ["22.01_cleaning_contract"]);
+    }
+
+    #[test]
+    fn test_serialized_as_list() {
+        let ids: Ids = vec![jd(None, 12, 2, "_b"), jd(None, 12, 1, "_a")].into();
+        let text = ron::to_string(&ids).unwrap();
+        assert!(text.starts_with('['));
+        let read: Ids = ron::from_str(&text).unwrap();
+        assert_eq!(read, ids);
+        assert_eq!(numbers(&read), ["12.01_a", "12.02_b"]);
+    }
+
+    /// Compare `Ids` with keeping the numbers in a sorted `Vec`, which is
+    /// how `System` used to keep them.
+    ///
+    /// Run it with `cargo test --release bench_ids -- --ignored --nocapture`.
+    #[test]
+    #[ignore = "benchmark"]
+    fn bench_ids() {
+        // 20 projects of 100 categories of 20 ids, added out of order.
+        let mut list = Vec::new();
+        for id in 1..=20 {
+            for project in 100..120 {
+                for category in 0..100 {
+                    list.push(jd(Some(project), category, id, "_some_label"));
+                }
+            }
+        }
+        let lookups: Vec<JdNumber> = list.iter().step_by(97).cloned().collect();
+
+        let start = Instant::now();
+        let mut vec: Vec<JdNumber> = Vec::new();
+        for jd in list.iter().cloned() {
+            if let Err(pos) = vec.binary_search(&jd) {
+                vec.insert(pos, jd);
+            }
+        }
+        let vec_add = start.elapsed();
+        let start = Instant::now();
+        let mut found = 0;
+        for jd in &lookups {
+            found += vec
+                .iter()
+                .filter(|other| other.project == jd.project && other.category == jd.category)
+                .count();
+        }
+        let vec_category = start.elapsed();
+
+        let start = Instant::now();
+        let mut ids = Ids::default();
+        for jd in list.iter().cloned() {
+            if !ids.contains(&jd) {
+                ids.insert(jd);
+            }
+        }
+        let ids_add = start.elapsed();
+        let start = Instant::now();
+        let mut ids_found = 0;
+        for jd in &lookups {
+            ids_found += ids.in_category(jd.project, jd.category).count();
+        }
+        let ids_category = start.elapsed();
+
+        assert_eq!(found, ids_found);
+        println!("{} numbers, {} category lookups", list.len(), lookups.len());
+        println!("adding:   Vec {:>10.1?}   Ids {:>10.1?}", vec_add, ids_add);
+        println!(
+            "category: Vec {:>10.1?}   Ids {:>10.1?}",
+            vec_category, ids_category
+        );
+    }
+}
@@ -1,16 +1,64 @@
-use crate::jdnumber::JdNumber;
+use crate::jdnumber::{
+    area_range, category_number, project_number, JdNumber, Layout, Location, NUMBER_EX,
+};
+use crate::usage::Usage;
 use regex::Regex;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
 use std::path;
 use std::path::PathBuf;
+use std::sync::LazyLock;
+
+mod ids;
+mod node;
+mod tree;
+pub use ids::Ids;
+pub use node::Node;
+pub use tree::{Depth, TreeOptions};
+
+/// A project as typed, like `101`.
+static PROJECT_INPUT_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d\d\d)$").expect("Hardcoded regex is valid."));
+/// A category as typed, like `12` or `101.12`.
+pub(crate) static CATEGORY_INPUT_EX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(\d\d\d)?\.?(\d\d)$").expect("Hardcoded regex is valid."));
+/// An area as typed, like `10-19` or `101.10-19`.
+pub(crate) static AREA_INPUT_EX: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^(?:(\d\d\d)\.)?(\d)0-(\d)9$").expect("Hardcoded regex is valid.")
+});
 
 /// A Johnny Decimal system.
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct System {
+    /// The version of the index format.
+    ///
+    /// Older indexes are upgraded when they are read; see `storage::migrate`.
+    pub version: u32,
     /// The root path of the Johnny Decimal system.
+    #[serde(with = "crate::jdnumber::lossless_path")]
     pub path: path::PathBuf,
-    /// A list of Johnny Decimal numbers.
-    pub id: Vec<JdNumber>,
+    /// How the folders of the system are laid out on disk.
+    #[serde(default)]
+    pub layout: Layout,
+    /// Area labels, keyed by the first number of the area.
+    ///
+    /// These are used when the layout has no area folders.
+    #[serde(default)]
+    pub area_labels: BTreeMap<u32, String>,
+    /// Category labels, keyed by the category number.
+    ///
+    /// These are used when the layout has no category folders.
+    #[serde(default)]
+    pub category_labels: BTreeMap<u32, String>,
+    /// The Johnny Decimal numbers of the system.
+    pub id: Ids,
+    /// How much is on disk under each number, keyed by the number.
+    ///
+    /// This is a cache for `jd du`; see `usage::measure`.
+    #[serde(default)]
+    pub usage: BTreeMap<String, Usage>,
 }
 
 impl System {
@@ -19,18 +67,119 @@ impl System {
     /// This adds an id to the system only if it is not a
     /// duplicate; otherwise, it returns `Err()`.
     pub fn add_id(&mut self, id: JdNumber) -> Result<(), &str> {
-        match self.id.binary_search(&id) {
-            Ok(_pos) => return Err("Element already exists."),
-            Err(pos) => self.id.insert(pos, id),
-        };
+        if self.id.contains(&id) {
+            return Err("Element already exists.");
+        }
+        self.id.insert(id);
         return Ok(());
     }
 
     /// Create a new System.
     pub fn new(path: path::PathBuf) -> Self {
         System {
+            version: crate::storage::migrate::CURRENT_VERSION,
             path,
-            id: Vec::new(),
+            layout: Layout::Full,
+            area_labels: BTreeMap::new(),
+            category_labels: BTreeMap::new(),
+            id: Ids::default(),
+            usage: BTreeMap::new(),
+        }
+    }
+
+    /// Fill in the area and category labels of a JD number.
+    ///
+    /// Labels that are on disk are remembered in the system, and
+    /// labels that are not on disk are taken from what is remembered.
+    pub fn fill_labels(&mut self, jd: &mut JdNumber) {
+        let area = jd.category / 10 * 10;
+
+        if self.layout.has_areas() {
+            self.area_labels.insert(area, jd.area_label.clone());
+        } else if let Some(label) = self.area_labels.get(&area) {
+            jd.area_label = label.clone();
+        }
+
+        if self.layout.has_categories() {
+            self.category_labels
+                .insert(jd.category, jd.category_label.clone());
+        } else if let Some(label) = self.category_labels.get(&jd.category) {
+            jd.category_label = label.clone();
+        }
+    }
+
+    /// Get the full path of a JD number in this system.
+    pub fn get_path(&self, jd: &JdNumber) -> PathBuf {
+        self.path.join(jd.get_relative_path(self.layout))
+    }
+
+    /// Move or rename a file or folder in the system.
+    ///
+    /// The index is kept up to date: the JD number at `from` is removed,
+    /// and `to` is added if it is a JD number.
+    pub fn move_path(&mut self, from: &path::Path, to: &path::Path) -> Result<(), String> {
+        if to.exists() {
+            return Err(format!("{} already exists.", to.display()));
+        }
+        if let Some(parent) = to.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|_| format!("Could not create {}.", parent.display()))?;
+        }
+        fs::rename(from, to)
+            .map_err(|_| format!("Could not move {} to {}.", from.display(), to.display()))?;
+
+        let root = self.path.clone();
+        let layout = self.layout;
+        self.id
+            .retain(|jd| root.join(jd.get_relative_path(layout)) != from);
+
+        let relative = to.strip_prefix(&self.path).unwrap_or(to).to_path_buf();
+        if let Ok(mut jd) = JdNumber::from_path(relative, self.layout) {
+            jd.path = if to.is_file() {
+                Location::File(to.to_path_buf())
+            } else {
+                Location::Path(to.to_path_buf())
+            };
+            self.fill_labels(&mut jd);
+
+            // An entry with the same number that points at `to`, or whose
+            // folder is gone, is stale; for example when the folder was
+            // renamed by hand.
+            match self.id.get(&jd) {
+                Some(old) if self.get_path(old) == to || !self.get_path(old).exists() => {
+                    self.id.insert(jd);
+                }
+                _ => self.add_id(jd).map_err(|e| e.to_string())?,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Find the JD numbers with labels that have words starting with every word of `term`.
+    pub fn search_labels(&self, term: &str) -> Vec<&JdNumber> {
+        let mut words = term
+            .split(|c: char| !c.is_alphanumeric())
+            .filter(|word| !word.is_empty());
+        let mut found = match words.next() {
+            Some(word) => self.id.with_word(word),
+            None => return Vec::new(),
+        };
+        for word in words {
+            let matching = self.id.with_word(word);
+            found.retain(|jd| matching.binary_search(jd).is_ok());
+        }
+        found
+    }
+
+    /// Get the path on disk of the primary location of a JD number.
+    ///
+    /// Relative mirror paths are taken to be relative to the system root.
+    pub fn get_primary_path(&self, jd: &JdNumber) -> Result<PathBuf, &str> {
+        match jd.primary_location() {
+            Some(Location::Path(_)) | Some(Location::File(_)) => Ok(self.get_path(jd)),
+            Some(Location::Mirror(path)) => Ok(self.path.join(path)),
+            _ => Err("JD number has no location on disk."),
         }
     }
 
@@ -80,66 +229,58 @@ impl System {
     ///
     /// It returns a tuple of the project, category, and id.
     fn parse_jd_input(input: String) -> (Option<u32>, Option<u32>, Option<u32>) {
-        // PRO.AC or AC
-        let cat_ex = Regex::new(r"^(\d\d\d)?\.?(\d\d)$").expect("Hardcoded regex is valid.");
-        // PRO
-        let project_ex = Regex::new(r"^(\d\d\d)$").expect("Hardcoded regex is valid.");
-        // PRO.AC.ID or AC.ID
-        let jd_ex = Regex::new(r"^(\d\d\d)?\.?(\d\d)\.(\d\d)$").expect("Hardcoded regex is valid.");
-
         let mut project: Option<u32> = None;
         let mut category: Option<u32> = None;
         let mut id: Option<u32> = None;
 
-        match cat_ex.captures(&input) {
-            Some(caps) => {
-                project = caps.get(1).map(|v| v.as_str().parse().unwrap());
-                category = caps.get(2).map(|v| v.as_str().parse().unwrap());
-            }
-            None => {}
-        };
+        if let Some(caps) = CATEGORY_INPUT_EX.captures(&input) {
+            project = caps.get(1).map(|v| v.as_str().parse().unwrap());
+            category = caps.get(2).map(|v| v.as_str().parse().unwrap());
+        }
 
-        match project_ex.captures(&input) {
-            Some(caps) => {
-                project = caps.get(1).map(|v| v.as_str().parse().unwrap());
-            }
-            None => {}
-        };
+        if let Some(caps) = PROJECT_INPUT_EX.captures(&input) {
+            project = caps.get(1).map(|v| v.as_str().parse().unwrap());
+        }
 
-        match jd_ex.captures(&input) {
-            Some(caps) => {
-                project = caps.get(1).map(|v| v.as_str().parse().unwrap());
-                category = caps.get(2).map(|v| v.as_str().parse().unwrap());
-                id = caps.get(3).map(|v| v.as_str().parse().unwrap());
-            }
-            None => {}
-        };
+        // PRO.AC.ID or AC.ID
+        if let Some(caps) = NUMBER_EX.captures(&input) {
+            project = caps.get(1).map(|v| v.as_str().parse().unwrap());
+            category = caps.get(2).map(|v| v.as_str().parse().unwrap());
+            id = caps.get(3).map(|v| v.as_str().parse().unwrap());
+        }
 
-        return (project, category, id);
+        (project, category, id)
     }
 
-    /// Display a johnny decimal system.
+    /// Select the JD numbers that match a Johnny Decimal input.
     ///
     /// The input should be a Johnny Decimal number, or a partial Johnny Decimal number.
     /// The types of numbers can be:
     /// - PRO.AC.ID or AC.ID
     /// - PRO
     /// - AC/PRO.AC
-    ///
-    /// For example,
-    /// `display(Some(String::from("50.43")))` should display the Johnny Decimal
-    /// `50.43`.
+    /// - AC-AC/PRO.AC-AC, an area like `10-19`
     ///
     /// If `None`, an empty string, or a string with some other giberish
-    /// is input, the whole Johnny Decimal system will be displayed.
-    pub fn display(&self, input: Option<String>) -> Result<String, &str> {
+    /// is input, every number is selected.
+    pub fn select(&self, input: Option<String>) -> Result<Vec<&JdNumber>, &str> {
         // let mut project: Option<u32> = None;
         // let mut category: Option<u32> = None;
         // let mut id: Option<u32> = None;
 
-        let (project, category, id) = System::parse_jd_input(input.unwrap_or("".to_string()));
+        let input = input.unwrap_or("".to_string());
+        if let Some(caps) = AREA_INPUT_EX.captures(&input) {
+            if caps[2] != caps[3] {
+                return Err("Areas are x0-x9.");
+            }
+            let project = caps.get(1).map(|v| v.as_str().parse().unwrap());
+            let area = caps[2].parse::<u32>().unwrap() * 10;
+            return Ok(self.id.in_area(project, area).collect());
+        }
+
+        let (project, category, id) = System::parse_jd_input(input);
 
-        let mut jd_list: Vec<&JdNumber>;
+        let jd_list: Vec<&JdNumber>;
 
         // if is a full decimal number, show it.
         if id.is_some() && category.is_some() {
@@ -155,96 +296,20 @@ impl System {
             )
             .expect("Manual jd number is valid");
 
-            match self.id.binary_search(&to_find) {
-                Ok(pos) => jd_list = vec![&self.id[pos]],
-                Err(_pos) => return Err("Cannot find JD number."),
+            match self.id.get(&to_find) {
+                Some(jd) => jd_list = vec![jd],
+                None => return Err("Cannot find JD number."),
             };
             // otherwise, if it is a category, show it.
-        } else if category.is_some() {
-            jd_list = Vec::new();
-            for item in &self.id {
-                if item.category == category.unwrap() && item.project == project {
-                    jd_list.push(&item);
-                }
-            }
+        } else if let Some(category) = category {
+            jd_list = self.id.in_category(project, category).collect();
         } else if project.is_some() {
-            jd_list = Vec::new();
-            for item in &self.id {
-                if item.project == project {
-                    jd_list.push(&item);
-                }
-            }
+            jd_list = self.id.in_project(project).collect();
         } else {
-            jd_list = Vec::new();
-            for item in &self.id {
-                jd_list.push(&item);
-            }
+            jd_list = self.id.iter().collect();
         }
 
-        // display the filtered numbers
-        let mut output = String::new();
-        let mut area_string = String::new();
-        let mut category_string = String::new();
-        let mut project_string: Option<String> = None;
-        for i in jd_list {
-            if i.project_label != project_string {
-                project_string = i.project_label.clone();
-                output.push_str(
-                    format!(
-                        "{}{}",
-                        i.project.unwrap(),
-                        &i.project_label.clone().unwrap_or(String::new())
-                    )
-                    .as_str(),
-                );
-                output.push_str("\n");
-            }
-            if i.area_label != area_string {
-                area_string = i.area_label.clone();
-                output.push_str(format!("  {}", &i.get_area().as_str()).as_str());
-                output.push_str("\n");
-            }
-            if i.category_label != category_string {
-                category_string = i.category_label.clone();
-                output.push_str(format!("    {}{}", i.category, &i.category_label).as_str());
-                output.push_str("\n");
-            }
-            output.push_str(format!("      {}", i.to_string()).as_str());
-            output.push_str("\n");
-        }
-
-        return Ok(output);
-    }
-
-    /// Filter by PRO, AC, and/or ID.
-    ///
-    /// Given PRO, AC, and ID arguments, this returns
-    /// all the JD numbers that match.  If a parameter is none
-    /// it is not filtered by.
-    fn filter_id(
-        &self,
-        project: Option<u32>,
-        category: Option<u32>,
-        id: Option<u32>,
-    ) -> Vec<&JdNumber> {
-        let mut to_return: Vec<&JdNumber> = Vec::new();
-        for i in &self.id {
-            if i.project != project {
-                continue;
-            }
-            if category.is_some() {
-                if category.unwrap() != i.category {
-                    continue;
-                }
-            }
-            if id.is_some() {
-                if id.unwrap() != i.id {
-                    continue;
-                }
-            }
-            to_return.push(&i);
-        }
-        return to_return;
+        Ok(jd_list)
     }
 
     /// Add an id from a string.
@@ -258,11 +323,15 @@ impl System {
             return Err("Could not find category.");
         }
 
-        let mut numbers = self.filter_id(project, category, None);
-        numbers.sort();
+        // the numbers of a category are in order, so the last is highest.
+        let number = match self.id.in_category(project, category.unwrap()).next_back() {
+            Some(number) => number,
+            None => return Err("Category has no numbers to add after."),
+        };
 
-        // now the last number should be highest.
-        let number = numbers[numbers.len() - 1];
+        if number.id >= number.max_id() {
+            return Err("Category is full.  Use `jd extend` to give it three digit ids.");
+        }
 
         let mut jd = match JdNumber::new(
             &number.area_label,
@@ -277,19 +346,113 @@ impl System {
             Ok(x) => x,
             Err(_) => return Err("Could not create JD number."),
         };
+        jd.project_area_label = number.project_area_label.clone();
+        jd.extended = number.extended;
 
-        jd.path = crate::jdnumber::Location::Path(self.path.join(jd.get_relative_path()));
+        jd.path = Location::Path(self.get_path(&jd));
 
         self.add_id(jd)?;
 
         return Ok(());
     }
 
+    /// Plan extending a category, so that its ids have three digits.
+    ///
+    /// The category is a PRO.AC or AC number, like for
+    /// [`System::add_id_from_str`].  This returns each number that is not
+    /// extended yet, with what it becomes; see [`System::renumber`].
+    pub fn plan_extend(&self, category: String) -> Result<Vec<(JdNumber, JdNumber)>, &str> {
+        let (project, category, id) = System::parse_jd_input(category);
+        let category = match (category, id) {
+            (Some(category), None) => category,
+            _ => return Err("Could not find category."),
+        };
+
+        Ok(self
+            .id
+            .in_category(project, category)
+            .filter(|jd| !jd.extended)
+            .map(|jd| {
+                let mut extended = jd.clone();
+                extended.extended = true;
+                (jd.clone(), extended)
+            })
+            .collect())
+    }
+
+    /// Give a JD number another number, moving its folder or file to match.
+    ///
+    /// Everything else about the number, like its other locations, is kept.
+    pub fn renumber(&mut self, from: &JdNumber, mut to: JdNumber) -> Result<(), String> {
+        let old_path = self.get_path(from);
+        let new_path = self.get_path(&to);
+
+        // Folders and files are named after the number, so they move with it.
+        to.path = match &from.path {
+            Location::Path(_) => Location::Path(new_path.clone()),
+            Location::File(_) => Location::File(new_path.clone()),
+            other => other.clone(),
+        };
+        if to.path.path() == Some(new_path.as_path()) && old_path.exists() {
+            if new_path.exists() {
+                return Err(format!("{} already exists.", new_path.display()));
+            }
+            fs::rename(&old_path, &new_path).map_err(|_| {
+                format!(
+                    "Could not move {} to {}.",
+                    old_path.display(),
+                    new_path.display()
+                )
+            })?;
+        }
+
+        self.id.remove(from);
+        self.id.insert(to);
+        Ok(())
+    }
+
+    /// Find the project, area, category and id that a path is in.
+    ///
+    /// The path can be anything inside the system, like a file deep in an
+    /// id folder.  Returns `None` if the path is not in the system.
+    pub fn which(&self, path: &path::Path) -> Option<Place> {
+        let relative = path.strip_prefix(&self.path).ok()?;
+        let mut place = Place::default();
+
+        let mut so_far = PathBuf::new();
+        for component in relative.components() {
+            so_far.push(component);
+            // What is inside an id belongs to it.
+            if let Ok(jd) = JdNumber::from_path(so_far.clone(), self.layout) {
+                place.id = Some(self.id.get(&jd).cloned().unwrap_or(jd));
+                break;
+            }
+            let Some(name) = component.as_os_str().to_str() else {
+                continue;
+            };
+            if area_range(name).is_some() {
+                place.area = Some(name.to_string());
+            } else if category_number(name).is_some() {
+                place.category = Some(name.to_string());
+            } else if project_number(name).is_some() {
+                place.project = Some(name.to_string());
+            }
+        }
+
+        // The id knows its area and category, even in layouts without their folders.
+        if let Some(jd) = &place.id {
+            place.project = jd.get_project();
+            place.area = Some(jd.get_area());
+            place.category = Some(jd.get_category());
+        }
+        Some(place)
+    }
+
     /// Get an id from the system.
     pub fn get_id(&self, id: JdNumber) -> Result<JdNumber, &str> {
-        match self.id.binary_search(&id) {
-            Ok(index) => Ok(self.id[index].clone()),
-            Err(_) => Err("Could not find JD"),
+        match self.id.get(&id) {
+            Some(jd) => Ok(jd.clone()),
+            None => Err("Could not find JD"),
         }
     }
 
@@ -310,19 +473,31 @@ impl System {
     // }
 }
 
+/// Where a path is in a Johnny Decimal system.
+///
+/// Each part is the name of its folder, like `12_payroll`, and is `None`
+/// if the path is not in one.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Place {
+    pub project: Option<String>,
+    pub area: Option<String>,
+    pub category: Option<String>,
+    pub id: Option<JdNumber>,
+}
+
 impl std::fmt::Display for System {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut to_write = String::new();
         let mut area_string = String::new();
         let mut category_string = String::new();
         for i in &self.id {
-            if i.area_label != area_string {
-                area_string = i.area_label.clone();
+            if i.get_area() != area_string {
+                area_string = i.get_area();
                 to_write.push_str(i.get_area().as_str());
                 to_write.push_str("\n");
             }
-            if i.category_label != category_string {
-                category_string = i.category_label.clone();
+            if format!("{}{}", i.category, i.category_label) != category_string {
+                category_string = format!("{}{}", i.category, i.category_label);
                 to_write.push_str(format!("  {}{}", i.category, &i.category_label).as_str());
                 to_write.push_str("\n");
             }
@@ -336,9 +511,13 @@ impl std::fmt::Display for System {
 
 #[cfg(test)]
 mod tests {
+    use crate::testing::{system_from_paths, TempRoot};
     use colored::Colorize;
 
-    use crate::{jdnumber::JdNumber, system::System};
+    use crate::{
+        jdnumber::{JdNumber, Layout, Location},
+        system::{Place, System},
+    };
     use std::path::PathBuf;
 
     #[test]
@@ -438,17 +617,16 @@ mod tests {
         // assert that the orders are the same.
         assert_eq!(system1.id, system2.id);
 
-        let mut system1_sorted = system1.id.clone();
-        system1_sorted.sort();
         // assert that they are sorted.
-        assert_eq!(system1.id, system1_sorted);
+        let numbers: Vec<&JdNumber> = system1.id.iter().collect();
+        assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
     }
 
     /// Create a test system
     fn create_sample_system() -> System {
         let text = r#"
-(path:"/home/calvin/200-299_programming/johnnydecimal/jd",
-projects:[],
+(version:4,
+path:"/home/calvin/200-299_programming/johnnydecimal/jd",
 id:[(project:None,category:12,id:1,label:"_sept_payroll",area_label:"_finance",category_label:"_payroll",path:Path("jd/10-19_finance/12_payroll/12.01_sept_payroll")),
 	(project:None,category:12,id:2,label:"_oct_payroll",area_label:"_finance",category_label:"_payroll",path:Path("jd/10-19_finance/12_payroll/12.02_oct_payroll")),
 	(project:None,category:22,id:1,label:"_cleaning_contract",area_label:"_admin",category_label:"_contracts",path:Path("jd/20-29_admin/22_contracts/22.01_cleaning_contract")),
@@ -458,51 +636,226 @@ id:[(project:None,category:12,id:1,label:"_sept_payroll",area_label:"_finance",c
         let system: System = ron::from_str(text).expect("Hardcoded value is valid.");
         return system;
     }
+    /// Create a test system with numbers in projects too
+    fn create_mixed_system() -> System {
+        let mut system = create_sample_system();
+        for path in [
+            "jd/100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page",
+            "jd/100-199_projects/101_website/10-19_design/12_mockups/101.12.02_about_page",
+            "jd/100-199_projects/101_website/20-29_build/21_code/101.21.01_server",
+            "jd/102_garden/20-29_build/21_code/102.21.01_shed",
+        ] {
+            let jd = JdNumber::try_from(PathBuf::from(path)).unwrap();
+            system.add_id(jd).unwrap();
+        }
+        system
+    }
+
+    #[test]
+    fn test_add_project_id() {
+        let mut system = create_mixed_system();
+        system
+            .add_id_from_str("101.12".to_string(), "_contact_page".to_string())
+            .unwrap();
+        let jd = system
+            .get_id(JdNumber::try_from(String::from("101.12.03")).unwrap())
+            .unwrap();
+        assert_eq!(jd.to_string(), "101.12.03_contact_page");
+        assert_eq!(
+            system.get_path(&jd),
+            system.path.join(
+                "100-199_projects/101_website/10-19_design/12_mockups/101.12.03_contact_page"
+            )
+        );
+
+        // projects without a project area
+        system
+            .add_id_from_str("102.21".to_string(), "_greenhouse".to_string())
+            .unwrap();
+        let jd = system
+            .get_id(JdNumber::try_from(String::from("102.21.02")).unwrap())
+            .unwrap();
+        assert_eq!(
+            system.get_path(&jd),
+            system
+                .path
+                .join("102_garden/20-29_build/21_code/102.21.02_greenhouse")
+        );
+
+        // the same category outside the project is separate
+        system
+            .add_id_from_str("12".to_string(), "_nov_payroll".to_string())
+            .unwrap();
+        assert!(system
+            .get_id(JdNumber::try_from(String::from("12.03")).unwrap())
+            .is_ok());
+
+        assert!(system
+            .add_id_from_str("103.12".to_string(), "_x".to_string())
+            .is_err());
+    }
+
+    #[test]
+    fn test_layout_labels() {
+        let mut system = System::new(PathBuf::from("~"));
+        system.layout = Layout::NoArea;
+        system.area_labels.insert(10, "_finance".to_string());
+
+        let mut jd = JdNumber::from_path(
+            PathBuf::from("12_payroll/12.01_sept_payroll"),
+            Layout::NoArea,
+        )
+        .unwrap();
+        system.fill_labels(&mut jd);
+
+        assert_eq!(jd.area_label, "_finance");
+        assert_eq!(jd.get_area(), "10-19_finance");
+        assert_eq!(system.category_labels.get(&12).unwrap(), "_payroll");
+        assert_eq!(
+            system.get_path(&jd),
+            PathBuf::from("~/12_payroll/12.01_sept_payroll")
+        );
+    }
+
     #[test]
-    fn test_show() {
+    fn test_get_primary_path() {
         let system = create_sample_system();
+        let mut jd = system.id.iter().next().unwrap().clone();
+        assert_eq!(
+            system.get_primary_path(&jd).unwrap(),
+            system
+                .path
+                .join("10-19_finance/12_payroll/12.01_sept_payroll")
+        );
+
+        jd.path = Location::Url("https://example.com".to_string());
+        assert!(system.get_primary_path(&jd).is_err());
+
+        jd.locations
+            .push(Location::Mirror(PathBuf::from("/mnt/cloud/12.01")));
+        assert_eq!(
+            system.get_primary_path(&jd).unwrap(),
+            PathBuf::from("/mnt/cloud/12.01")
+        );
+    }
+
+    #[test]
+    fn test_move_path() {
+        let root = TempRoot::new("move_path");
+        let from = root.join("10-19_finance/12_payroll/12.01_sept_payroll");
+        let to = root.join("10-19_finance/13_bookkeeping/13.01_sept_payroll");
+        std::fs::create_dir_all(&from).unwrap();
+
+        let mut system = system_from_paths(&root, &["10-19_finance/12_payroll/12.01_sept_payroll"]);
+
+        system.move_path(&from, &to).unwrap();
+
+        assert!(!from.exists());
+        assert!(to.is_dir());
+        assert_eq!(system.id.len(), 1);
+        let moved = system.id.iter().next().unwrap();
+        assert_eq!(moved.category, 13);
+        assert_eq!(moved.category_label, "_bookkeeping");
+        assert_eq!(system.get_path(moved), to);
+
+        // moving onto something that exists fails
+        std::fs::create_dir_all(&from).unwrap();
+        assert!(system.move_path(&from, &to).is_err());
+    }
+
+    #[test]
+    fn test_extend() {
+        let root = TempRoot::new("extend");
+        let payroll = root.join("10-19_finance/12_payroll");
+        std::fs::create_dir_all(payroll.join("12.01_sept_payroll")).unwrap();
+        std::fs::create_dir_all(payroll.join("12.02_oct_payroll")).unwrap();
+
+        let mut system = system_from_paths(
+            &root,
+            &[
+                "10-19_finance/12_payroll/12.01_sept_payroll",
+                "10-19_finance/12_payroll/12.02_oct_payroll",
+            ],
+        );
+
+        let plan = system.plan_extend("12".to_string()).unwrap();
+        assert_eq!(plan.len(), 2);
+        for (from, to) in plan {
+            system.renumber(&from, to).unwrap();
+        }
+        assert!(payroll.join("12.001_sept_payroll").is_dir());
+        assert!(payroll.join("12.002_oct_payroll").is_dir());
+        assert!(!payroll.join("12.01_sept_payroll").exists());
+        assert!(system.plan_extend("12".to_string()).unwrap().is_empty());
 
-        // test giving no argument
-        let mut left = system.display(None).unwrap();
-        let full_system = "  10-19_finance
-    12_payroll
-      12.01_sept_payroll
-      12.02_oct_payroll
-  20-29_admin
-    22_contracts
-      22.01_cleaning_contract
-      22.02_office_lease\n";
-
-        assert_eq!(left, full_system);
-
-        // test giving a category
-        let category = "  10-19_finance
-    12_payroll
-      12.01_sept_payroll
-      12.02_oct_payroll\n";
-        left = system.display(Some(String::from("12"))).unwrap();
-        assert_eq!(left, category);
-
-        // test giving a complete AC.ID number
-        let jd_number = "  20-29_admin
-    22_contracts
-      22.01_cleaning_contract\n";
-        left = system.display(Some(String::from("22.01"))).unwrap();
-        assert_eq!(left, jd_number);
-
-        // test giving giberish
-        left = system
-            .display(Some(String::from("this-is_some~giberish")))
+        // new ids follow, and go past 99
+        for i in 3..=100 {
+            system
+                .add_id_from_str("12".to_string(), format!("_payroll_{}", i))
+                .unwrap();
+        }
+        let last = system.id.iter().last().unwrap();
+        assert_eq!(last.number(), "12.100");
+        assert!(last.extended);
+    }
+
+    #[test]
+    fn test_which() {
+        let system = create_mixed_system();
+        let root = system.path.clone();
+
+        let place = system
+            .which(&root.join("10-19_finance/12_payroll/12.02_oct_payroll/slips/slip.pdf"))
+            .unwrap();
+        assert_eq!(place.project, None);
+        assert_eq!(place.area, Some(String::from("10-19_finance")));
+        assert_eq!(place.category, Some(String::from("12_payroll")));
+        assert_eq!(place.id.unwrap().to_string(), "12.02_oct_payroll");
+
+        // not in an id
+        let place = system
+            .which(&root.join("10-19_finance/12_payroll"))
             .unwrap();
-        assert_eq!(left, full_system);
+        assert_eq!(place.category, Some(String::from("12_payroll")));
+        assert_eq!(place.id, None);
+        assert_eq!(system.which(&root), Some(Place::default()));
+
+        let place = system
+            .which(&root.join(
+                "100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page/a.png",
+            ))
+            .unwrap();
+        assert_eq!(place.project, Some(String::from("101_website")));
+        assert_eq!(place.id.unwrap().number(), "101.12.01");
+
+        assert_eq!(system.which(&PathBuf::from("/somewhere/else")), None);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_non_utf8_round_trip() {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
 
-        // test giving an empty string
-        left = system.display(Some(String::from(""))).unwrap();
-        assert_eq!(left, full_system);
+        let root = PathBuf::from(OsStr::from_bytes(b"/backup/caf\xe9/jd"));
+        let mut system = System::new(root.clone());
+        let jd_path = root.join("10-19_finance/12_payroll/12.01_sept_payroll");
+        system
+            .add_id(JdNumber::try_from(jd_path.clone()).unwrap())
+            .unwrap();
+
+        let text = ron::to_string(&system).unwrap();
+        let read: System = ron::from_str(&text).unwrap();
+
+        assert_eq!(read.path, root);
+        assert_eq!(read.id.iter().next().unwrap().path, Location::Path(jd_path));
     }
 
     #[test]
     fn test_colorize() {
+        // colour is off when the tests are not run in a terminal
+        colored::control::set_override(true);
+
         let string1 = "Hello world.".red();
         let string2 = "Hello world.";
 
@@ -587,7 +940,7 @@ id:[(project:None,category:12,id:1,label:"_sept_payroll",area_label:"_finance",c
             .unwrap();
 
         assert_eq!(
-            system.id[2], //because it is sorted, it is the third element.
+            *system.id.iter().nth(2).unwrap(), //because it is sorted, it is the third element.
             JdNumber::new(
                 "_finance",
                 "_payroll",
@@ -610,7 +963,7 @@ id:[(project:None,category:12,id:1,label:"_sept_payroll",area_label:"_finance",c
             .unwrap();
 
         assert_eq!(
-            system.id[3],
+            *system.id.iter().nth(3).unwrap(),
             JdNumber::new(
                 "_finance",
                 "_payroll",
@@ -0,0 +1,43 @@
+/// A project, area, category or id of a system, with the parts inside it.
+///
+/// Trees of these are built from JD numbers in order, so the parts of a
+/// level are added one after the other.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node<T> {
+    /// The name of the part, like `10-19_finance` or `12.01_sept_payroll`.
+    pub name: String,
+    pub value: T,
+    pub children: Vec<Node<T>>,
+}
+
+impl<T: Default> Node<T> {
+    pub fn new(name: String) -> Self {
+        Node {
+            name,
+            value: T::default(),
+            children: Vec::new(),
+        }
+    }
+
+    /// Get the last child, adding one called `name` if it is not called that.
+    pub fn child(&mut self, name: String) -> &mut Node<T> {
+        if self.children.last().map(|c| &c.name) != Some(&name) {
+            self.children.push(Node::new(name));
+        }
+        self.children.last_mut().expect("A child was just added.")
+    }
+
+    /// Get the node down the path of `names`, adding the parts that are missing.
+    ///
+    /// `visit` is called with the value of every node on the way, from
+    /// this one down, like to add to the totals of each level.
+    pub fn descend(&mut self, names: Vec<String>, mut visit: impl FnMut(&mut T)) -> &mut Node<T> {
+        visit(&mut self.value);
+        let mut node = self;
+        for name in names {
+            node = node.child(name);
+            visit(&mut node.value);
+        }
+        node
+    }
+}
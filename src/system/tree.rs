@@ -0,0 +1,427 @@
+use super::{Node, System};
+use crate::jdnumber::JdNumber;
+use colored::Colorize;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// How deep to show a system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Depth {
+    /// Projects and areas.
+    Area,
+    /// Projects, areas and categories.
+    Category,
+    /// Everything down to the ids.
+    Id,
+}
+
+impl FromStr for Depth {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "area" => Ok(Depth::Area),
+            "category" => Ok(Depth::Category),
+            "id" => Ok(Depth::Id),
+            _ => Err(String::from("unknown depth.  Use area, category or id.")),
+        }
+    }
+}
+
+/// How to show a system as a tree.
+#[derive(Debug, Clone, Copy)]
+pub struct TreeOptions {
+    pub depth: Depth,
+    /// List every location of each id.
+    pub locations: bool,
+    /// List what is inside the folder of each id.
+    pub files: bool,
+    /// Colour the numbers, so they stand out from the labels.
+    pub color: bool,
+}
+
+impl Default for TreeOptions {
+    fn default() -> Self {
+        TreeOptions {
+            depth: Depth::Id,
+            locations: false,
+            files: false,
+            color: false,
+        }
+    }
+}
+
+/// A line of the tree, with the lines under it.
+///
+/// Projects, areas and categories have how many ids are under them.
+type Line = Node<Option<usize>>;
+
+/// Write the children of a line, each starting with `prefix`.
+fn render(line: &Line, prefix: &str, color: bool, output: &mut String) {
+    for (i, child) in line.children.iter().enumerate() {
+        let last = i + 1 == line.children.len();
+        output.push_str(prefix);
+        output.push_str(if last { "└── " } else { "├── " });
+        output.push_str(&child.name);
+        if let Some(ids) = child.value {
+            let count = format!(" ({})", ids);
+            if color {
+                output.push_str(&count.dimmed().to_string());
+            } else {
+                output.push_str(&count);
+            }
+        }
+        output.push('\n');
+
+        let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+        render(child, &prefix, color, output);
+    }
+}
+
+/// Colour the number at the start of a name, like the `12` of `12_payroll`.
+fn paint(name: String, color: bool) -> String {
+    if !color {
+        return name;
+    }
+    let split = name
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '-'))
+        .unwrap_or(name.len());
+    let (number, label) = name.split_at(split);
+    format!("{}{}", number.cyan().bold(), label)
+}
+
+/// Add everything inside `folder` to `node`, in order of name.
+fn add_files(node: &mut Line, folder: &Path) {
+    let mut entries: Vec<fs::DirEntry> = match fs::read_dir(folder) {
+        Ok(entries) => entries.filter_map(|e| e.ok()).collect(),
+        Err(_) => return,
+    };
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let mut child = Line::new(entry.file_name().to_string_lossy().into_owned());
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            add_files(&mut child, &entry.path());
+        }
+        node.children.push(child);
+    }
+}
+
+impl System {
+    /// Show part or all of a system as a tree.
+    ///
+    /// The input selects what to show, like in [`System::select`].
+    /// Projects, areas and categories show how many ids are in them.
+    pub fn tree(&self, input: Option<String>, options: TreeOptions) -> Result<String, &str> {
+        let mut root = Line::new(String::new());
+
+        for jd in self.select(input)? {
+            let mut names = jd.get_parents();
+            if options.depth == Depth::Area {
+                names.pop();
+            }
+            let names = names
+                .into_iter()
+                .map(|name| paint(name, options.color))
+                .collect();
+
+            let node = root.descend(names, |ids| *ids = Some(ids.unwrap_or(0) + 1));
+            if options.depth == Depth::Id {
+                node.children.push(self.id_node(jd, options));
+            }
+        }
+
+        let mut output = String::new();
+        render(&root, "", options.color, &mut output);
+        Ok(output)
+    }
+
+    /// Make the line of an id, with its locations and files under it.
+    fn id_node(&self, jd: &JdNumber, options: TreeOptions) -> Line {
+        let mut node = Line::new(paint(jd.to_string(), options.color));
+        if options.locations {
+            for location in jd.all_locations() {
+                let text = location.to_string();
+                let text = if options.color {
+                    text.dimmed().to_string()
+                } else {
+                    text
+                };
+                node.children.push(Line::new(text));
+            }
+        }
+        if options.files && !jd.path.is_file() {
+            add_files(&mut node, &self.get_path(jd));
+        }
+        node
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Depth, TreeOptions};
+    use crate::jdnumber::{JdNumber, Location};
+    use crate::system::System;
+    use crate::testing::{system_from_paths, TempRoot};
+    use std::fs;
+    use std::path::Path;
+
+    fn sample_system(root: &Path) -> System {
+        system_from_paths(
+            root,
+            &[
+                "10-19_finance/12_payroll/12.01_sept_payroll",
+                "10-19_finance/12_payroll/12.02_oct_payroll",
+                "10-19_finance/13_bookkeeping/13.01_ledger",
+                "20-29_admin/22_contracts/22.01_cleaning_contract",
+                "100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page",
+            ],
+        )
+    }
+
+    #[test]
+    fn test_tree() {
+        let system = sample_system(Path::new("/jd"));
+        let expected = "\
+├── 10-19_finance (3)
+│   ├── 12_payroll (2)
+│   │   ├── 12.01_sept_payroll
+│   │   └── 12.02_oct_payroll
+│   └── 13_bookkeeping (1)
+│       └── 13.01_ledger
+├── 20-29_admin (1)
+│   └── 22_contracts (1)
+│       └── 22.01_cleaning_contract
+└── 101_website (1)
+    └── 10-19_design (1)
+        └── 12_mockups (1)
+            └── 101.12.01_home_page
+";
+        assert_eq!(system.tree(None, TreeOptions::default()).unwrap(), expected);
+
+        let options = TreeOptions {
+            depth: Depth::Category,
+            ..TreeOptions::default()
+        };
+        let expected = "\
+└── 10-19_finance (3)
+    ├── 12_payroll (2)
+    └── 13_bookkeeping (1)
+";
+        assert_eq!(
+            system.tree(Some(String::from("10-19")), options).unwrap(),
+            expected
+        );
+
+        let options = TreeOptions {
+            depth: Depth::Area,
+            ..TreeOptions::default()
+        };
+        assert_eq!(
+            system.tree(Some(String::from("22")), options).unwrap(),
+            "└── 20-29_admin (1)\n"
+        );
+    }
+
+    /// Make a system with numbers both in and out of projects.
+    fn mixed_system() -> System {
+        system_from_paths(
+            Path::new("/jd"),
+            &[
+                "10-19_finance/12_payroll/12.01_sept_payroll",
+                "10-19_finance/12_payroll/12.02_oct_payroll",
+                "20-29_admin/22_contracts/22.01_cleaning_contract",
+                "20-29_admin/22_contracts/22.02_office_lease",
+                "100-199_projects/101_website/10-19_design/12_mockups/101.12.01_home_page",
+                "100-199_projects/101_website/10-19_design/12_mockups/101.12.02_about_page",
+                "100-199_projects/101_website/20-29_build/21_code/101.21.01_server",
+                "102_garden/20-29_build/21_code/102.21.01_shed",
+            ],
+        )
+    }
+
+    fn show(system: &System, input: &str) -> Result<String, String> {
+        system
+            .tree(Some(String::from(input)), TreeOptions::default())
+            .map_err(String::from)
+    }
+
+    #[test]
+    fn test_tree_select() {
+        let system = mixed_system();
+
+        // the numbers outside projects come first, then each project
+        let full_system = "\
+├── 10-19_finance (2)
+│   └── 12_payroll (2)
+│       ├── 12.01_sept_payroll
+│       └── 12.02_oct_payroll
+├── 20-29_admin (2)
+│   └── 22_contracts (2)
+│       ├── 22.01_cleaning_contract
+│       └── 22.02_office_lease
+├── 101_website (3)
+│   ├── 10-19_design (2)
+│   │   └── 12_mockups (2)
+│   │       ├── 101.12.01_home_page
+│   │       └── 101.12.02_about_page
+│   └── 20-29_build (1)
+│       └── 21_code (1)
+│           └── 101.21.01_server
+└── 102_garden (1)
+    └── 20-29_build (1)
+        └── 21_code (1)
+            └── 102.21.01_shed
+";
+        assert_eq!(
+            system.tree(None, TreeOptions::default()).unwrap(),
+            full_system
+        );
+        // giberish and nothing show everything too
+        assert_eq!(show(&system, "").unwrap(), full_system);
+        assert_eq!(show(&system, "this-is_some~giberish").unwrap(), full_system);
+
+        // categories and areas outside projects leave the projects out
+        let category = "\
+└── 10-19_finance (2)
+    └── 12_payroll (2)
+        ├── 12.01_sept_payroll
+        └── 12.02_oct_payroll
+";
+        assert_eq!(show(&system, "12").unwrap(), category);
+        let area = "\
+└── 20-29_admin (2)
+    └── 22_contracts (2)
+        ├── 22.01_cleaning_contract
+        └── 22.02_office_lease
+";
+        assert_eq!(show(&system, "20-29").unwrap(), area);
+        assert!(show(&system, "20-39").is_err());
+
+        let jd_number = "\
+└── 20-29_admin (1)
+    └── 22_contracts (1)
+        └── 22.01_cleaning_contract
+";
+        assert_eq!(show(&system, "22.01").unwrap(), jd_number);
+    }
+
+    #[test]
+    fn test_tree_projects() {
+        let system = mixed_system();
+
+        let project = "\
+└── 101_website (3)
+    ├── 10-19_design (2)
+    │   └── 12_mockups (2)
+    │       ├── 101.12.01_home_page
+    │       └── 101.12.02_about_page
+    └── 20-29_build (1)
+        └── 21_code (1)
+            └── 101.21.01_server
+";
+        assert_eq!(show(&system, "101").unwrap(), project);
+
+        let category = "\
+└── 102_garden (1)
+    └── 20-29_build (1)
+        └── 21_code (1)
+            └── 102.21.01_shed
+";
+        assert_eq!(show(&system, "102.21").unwrap(), category);
+
+        let jd_number = "\
+└── 101_website (1)
+    └── 10-19_design (1)
+        └── 12_mockups (1)
+            └── 101.12.02_about_page
+";
+        assert_eq!(show(&system, "101.12.02").unwrap(), jd_number);
+
+        // a project with no numbers shows nothing
+        assert_eq!(show(&system, "103").unwrap(), "");
+        assert!(show(&system, "101.12.09").is_err());
+    }
+
+    #[test]
+    fn test_tree_locations() {
+        let mut system = mixed_system();
+        let mut jd = system.id.iter().next().unwrap().clone();
+        jd.locations.push(Location::Physical {
+            description: "Box 3".to_string(),
+        });
+        system.id.insert(jd);
+
+        let options = TreeOptions {
+            locations: true,
+            ..TreeOptions::default()
+        };
+        let expected = "\
+└── 10-19_finance (1)
+    └── 12_payroll (1)
+        └── 12.01_sept_payroll
+            ├── folder: 10-19_finance/12_payroll/12.01_sept_payroll
+            └── physical: Box 3
+";
+        assert_eq!(
+            system.tree(Some(String::from("12.01")), options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_tree_unlabelled_project() {
+        let mut system = sample_system(Path::new("/jd"));
+        system
+            .add_id(JdNumber::try_from(String::from("102.22.01")).unwrap())
+            .unwrap();
+
+        // it stays under its project, instead of joining the areas of no project
+        let expected = "\
+└── 102 (1)
+    └── 20-29 (1)
+        └── 22 (1)
+            └── 102.22.01label
+";
+        assert_eq!(
+            system
+                .tree(Some(String::from("102")), TreeOptions::default())
+                .unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_tree_files() {
+        let root = TempRoot::new("tree");
+        let system = sample_system(&root);
+        let folder = root.join("10-19_finance/12_payroll/12.01_sept_payroll");
+        fs::create_dir_all(folder.join("scans")).unwrap();
+        fs::write(folder.join("scans/page_1.png"), "").unwrap();
+        fs::write(folder.join("slip.pdf"), "").unwrap();
+
+        let options = TreeOptions {
+            files: true,
+            ..TreeOptions::default()
+        };
+        let expected = "\
+└── 10-19_finance (1)
+    └── 12_payroll (1)
+        └── 12.01_sept_payroll
+            ├── scans
+            │   └── page_1.png
+            └── slip.pdf
+";
+        assert_eq!(
+            system.tree(Some(String::from("12.01")), options).unwrap(),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_depth_from_str() {
+        assert_eq!("category".parse::<Depth>(), Ok(Depth::Category));
+        assert!("folder".parse::<Depth>().is_err());
+    }
+}
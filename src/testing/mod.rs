@@ -0,0 +1,57 @@
+use crate::jdnumber::JdNumber;
+use crate::system::System;
+use std::fs;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+
+/// An empty temporary folder for a test.
+///
+/// The folder is removed when this is dropped, so it is cleaned up even
+/// when the test fails.
+pub struct TempRoot {
+    path: PathBuf,
+}
+
+impl TempRoot {
+    /// Make an empty temporary folder, named after `name` and this process.
+    pub fn new(name: &str) -> Self {
+        let path = std::env::temp_dir().join(format!("jd_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempRoot { path }
+    }
+}
+
+impl Deref for TempRoot {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl AsRef<Path> for TempRoot {
+    fn as_ref(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// Make a system at `root` with a JD number for each of `paths`.
+///
+/// The paths are relative to the root, and the labels of their projects,
+/// areas and categories are filled in, like indexing a system would.
+pub fn system_from_paths(root: &Path, paths: &[&str]) -> System {
+    let mut system = System::new(root.to_path_buf());
+    for path in paths {
+        let mut jd = JdNumber::try_from(PathBuf::from(path)).unwrap();
+        system.fill_labels(&mut jd);
+        system.add_id(jd).unwrap();
+    }
+    system
+}
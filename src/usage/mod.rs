@@ -0,0 +1,309 @@
+use crate::jdnumber::{JdNumber, Location};
+use crate::system::{Node, System};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::SystemTime;
+
+/// How much is filed on disk under a JD number.
+///
+/// These are kept in the index, so they only have to be counted again
+/// when something has changed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Usage {
+    /// The total size of the files, in bytes.
+    pub bytes: u64,
+    pub files: u64,
+    /// When the number's folder, or any folder inside it, was last changed.
+    ///
+    /// For a number that is a single file, when the file was last changed.
+    pub modified: SystemTime,
+}
+
+/// Get when a folder, or any folder inside it, was last changed.
+///
+/// Adding, removing or renaming anything changes the folder it is in,
+/// so if this is the same as when the folder was counted, the count is
+/// still right.  Only folders are looked up; files are skipped by the type
+/// their folder lists them with, which is what makes this faster than
+/// counting again.  Writing to a file in place does not change its folder,
+/// so that is missed until the number is counted again with `--refresh`.
+fn last_changed(path: &Path) -> Option<SystemTime> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let mut newest = metadata.modified().ok()?;
+    if !metadata.is_dir() {
+        return Some(newest);
+    }
+
+    for entry in fs::read_dir(path).ok()?.filter_map(|e| e.ok()) {
+        if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+            if let Some(changed) = last_changed(&entry.path()) {
+                newest = newest.max(changed);
+            }
+        }
+    }
+    Some(newest)
+}
+
+/// Count the files under a path and total their sizes.
+///
+/// Symbolic links are counted as files, and not followed.
+fn count(path: &Path) -> Option<Usage> {
+    let metadata = fs::symlink_metadata(path).ok()?;
+    let mut usage = Usage {
+        bytes: 0,
+        files: 0,
+        modified: metadata.modified().ok()?,
+    };
+    if !metadata.is_dir() {
+        usage.bytes = metadata.len();
+        usage.files = 1;
+        return Some(usage);
+    }
+
+    for entry in fs::read_dir(path).ok()?.filter_map(|e| e.ok()) {
+        if let Some(inner) = count(&entry.path()) {
+            usage.bytes += inner.bytes;
+            usage.files += inner.files;
+            // only folders count towards when it was last changed
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                usage.modified = usage.modified.max(inner.modified);
+            }
+        }
+    }
+    Some(usage)
+}
+
+/// Get the usage of some JD numbers, keyed by their number.
+///
+/// Usage cached in `system` is used when nothing has changed since it
+/// was counted, unless `refresh` is set.  Everything that is counted is
+/// cached in `system`, and the cache of numbers that are gone is dropped.
+/// Numbers that are not on disk have no usage.
+///
+/// Also returns how many numbers were counted.
+pub fn measure(
+    system: &mut System,
+    numbers: &[&JdNumber],
+    refresh: bool,
+) -> (BTreeMap<String, Usage>, usize) {
+    let system_ref = &*system;
+    let measured: Vec<(String, Option<Usage>, bool)> = numbers
+        .par_iter()
+        .filter(|jd| matches!(jd.path, Location::Path(_) | Location::File(_)))
+        .map(|jd| {
+            let number = jd.number();
+            let path = system_ref.get_path(jd);
+            if !refresh {
+                if let Some(cached) = system_ref.usage.get(&number) {
+                    if last_changed(&path) == Some(cached.modified) {
+                        return (number, Some(cached.clone()), false);
+                    }
+                }
+            }
+            (number, count(&path), true)
+        })
+        .collect();
+
+    let numbers: BTreeSet<String> = system.id.iter().map(|jd| jd.number()).collect();
+    system.usage.retain(|number, _| numbers.contains(number));
+
+    let mut usages = BTreeMap::new();
+    let mut counted = 0;
+    for (number, usage, was_counted) in measured {
+        if was_counted {
+            counted += 1;
+            match &usage {
+                Some(usage) => system.usage.insert(number.clone(), usage.clone()),
+                None => system.usage.remove(&number),
+            };
+        }
+        if let Some(usage) = usage {
+            usages.insert(number, usage);
+        }
+    }
+    (usages, counted)
+}
+
+/// How to sort the parts of a usage report.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortBy {
+    /// In Johnny Decimal order.
+    Number,
+    /// Largest first.
+    Size,
+    /// Most files first.
+    Files,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "number" => Ok(SortBy::Number),
+            "size" => Ok(SortBy::Size),
+            "files" => Ok(SortBy::Files),
+            _ => Err(String::from("unknown sort.  Use number, size or files.")),
+        }
+    }
+}
+
+/// How much is under a project, area, category or id in a usage report.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Total {
+    pub bytes: u64,
+    pub files: u64,
+}
+
+impl Node<Total> {
+    /// Sort the children, and everything in them.
+    pub fn sort(&mut self, by: SortBy) {
+        match by {
+            // They are built in number order.
+            SortBy::Number => {}
+            SortBy::Size => self
+                .children
+                .sort_by_key(|c| std::cmp::Reverse(c.value.bytes)),
+            SortBy::Files => self
+                .children
+                .sort_by_key(|c| std::cmp::Reverse(c.value.files)),
+        }
+        for child in &mut self.children {
+            child.sort(by);
+        }
+    }
+}
+
+/// Total the usage of JD numbers up through their categories, areas and projects.
+///
+/// The numbers have to be in order.  The returned node is the whole
+/// system; its children are the projects, and the areas of the numbers
+/// that are not in a project.
+pub fn tree(numbers: &[&JdNumber], usages: &BTreeMap<String, Usage>) -> Node<Total> {
+    let mut root = Node::new(String::new());
+    for jd in numbers {
+        let usage = usages.get(&jd.number());
+        let (bytes, files) = usage.map_or((0, 0), |u| (u.bytes, u.files));
+
+        let mut names = jd.get_parents();
+        names.push(jd.to_string());
+        root.descend(names, |total: &mut Total| {
+            total.bytes += bytes;
+            total.files += files;
+        });
+    }
+    root
+}
+
+/// Write a number of bytes for people to read, like `1.5 MiB`.
+pub fn human_size(bytes: u64) -> String {
+    const UNITS: [&str; 6] = ["B", "KiB", "MiB", "GiB", "TiB", "PiB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{human_size, measure, tree, SortBy};
+    use crate::jdnumber::JdNumber;
+    use crate::testing::{system_from_paths, TempRoot};
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn test_measure_and_cache() {
+        let root = TempRoot::new("usage");
+        let payroll = root.join("10-19_finance/12_payroll");
+        fs::create_dir_all(payroll.join("12.01_sept_payroll/scans")).unwrap();
+        fs::write(payroll.join("12.01_sept_payroll/slip.txt"), "12345").unwrap();
+        fs::write(payroll.join("12.01_sept_payroll/scans/a.txt"), "123").unwrap();
+        fs::create_dir_all(root.join("10-19_finance/13_bookkeeping")).unwrap();
+        fs::write(
+            root.join("10-19_finance/13_bookkeeping/13.01_ledger.csv"),
+            "1",
+        )
+        .unwrap();
+
+        let mut system = system_from_paths(
+            &root,
+            &[
+                "10-19_finance/12_payroll/12.01_sept_payroll",
+                "10-19_finance/13_bookkeeping/13.01_ledger.csv",
+            ],
+        );
+        let numbers: Vec<JdNumber> = system.id.iter().cloned().collect();
+        let numbers: Vec<&JdNumber> = numbers.iter().collect();
+
+        let (usages, counted) = measure(&mut system, &numbers, false);
+        assert_eq!(counted, 2);
+        assert_eq!(usages["12.01"].bytes, 8);
+        assert_eq!(usages["12.01"].files, 2);
+        assert_eq!(usages["13.01"].bytes, 1);
+        assert_eq!(system.usage.len(), 2);
+
+        // nothing changed, so the cache is used
+        let (cached, counted) = measure(&mut system, &numbers, false);
+        assert_eq!(counted, 0);
+        assert_eq!(cached, usages);
+        assert_eq!(measure(&mut system, &numbers, true).1, 2);
+
+        // a file that grows in place is missed until it is refreshed
+        let slip = fs::OpenOptions::new()
+            .append(true)
+            .open(payroll.join("12.01_sept_payroll/slip.txt"))
+            .unwrap();
+        std::io::Write::write_all(&mut &slip, b"67").unwrap();
+        assert_eq!(measure(&mut system, &numbers, false).1, 0);
+        assert_eq!(measure(&mut system, &numbers, true).0["12.01"].bytes, 10);
+
+        // a file added to a folder inside the id is counted
+        let scans = payroll.join("12.01_sept_payroll/scans");
+        fs::write(scans.join("b.txt"), "4").unwrap();
+        fs::File::open(&scans)
+            .unwrap()
+            .set_modified(SystemTime::now() + Duration::from_secs(60))
+            .unwrap();
+        let (grown, counted) = measure(&mut system, &numbers, false);
+        assert_eq!(counted, 1);
+        assert_eq!(grown["12.01"].bytes, 11);
+        assert_eq!(grown["12.01"].files, 3);
+
+        let mut tree = tree(&numbers, &grown);
+        assert_eq!(tree.value.bytes, 12);
+        assert_eq!(tree.value.files, 4);
+        let area = &tree.children[0];
+        assert_eq!(area.name, "10-19_finance");
+        assert_eq!(area.children[0].name, "12_payroll");
+        assert_eq!(area.children[0].children[0].name, "12.01_sept_payroll");
+        assert_eq!(area.children[1].value.bytes, 1);
+
+        tree.sort(SortBy::Files);
+        assert_eq!(tree.children[0].children[0].value.files, 3);
+    }
+
+    #[test]
+    fn test_sort_by() {
+        assert_eq!("size".parse::<SortBy>().unwrap(), SortBy::Size);
+        assert!("biggest".parse::<SortBy>().is_err());
+    }
+
+    #[test]
+    fn test_human_size() {
+        assert_eq!(human_size(512), "512 B");
+        assert_eq!(human_size(1536), "1.5 KiB");
+        assert_eq!(human_size(3 * 1024 * 1024 * 1024), "3.0 GiB");
+    }
+}